@@ -1,4 +1,4 @@
-use vc_8bit::{assembly, c_lang, vc_8bit::Computer};
+use vc_8bit::{assembly, c_lang, loader, vc_8bit::Computer};
 fn main() { 
     run_compiled_code_with_debugging();
 }
@@ -15,11 +15,9 @@ fn run_assembly_from_file() {
     run_assembly(full_contents);
 }
 fn run_binary() {
-    let full_contents = std::fs::read_to_string("src/test/test.bin").unwrap();
-    let contents = full_contents.replace(" ", "").replace("\n", "s").replace("\r", "");
-    let bytes = assembly::string_to_bytes(contents.as_str());
+    let image = loader::ProgramImage::from_file("src/test/test.bin");
     let mut computer: Computer = Computer::new();
-    computer.ram.insert_bytes(bytes);
+    image.load(&mut computer.ram);
     computer.run();
 }
 fn compile_and_run(value: String) {
@@ -37,13 +35,11 @@ fn run_compiled_code_with_debugging() {
     // array[CONSTANT]
     // value << CONSTANT
     let contents = std::fs::read_to_string("src/test/test.c").unwrap();
-    let fixed = c_lang::constants_and_bytes(&contents);
-    let lex = c_lang::get_lexer_lines(&fixed);
-    let par = c_lang::parse(lex);
-    for l in par.clone() { println!("{}", c_lang::fmt_expr(&l.unwrap())) }
+    let program = c_lang::parse(&c_lang::tokenize(&contents));
+    for stmt in &program { println!("{}", c_lang::fmt_stmt(stmt)) }
     
     println!("\n- ASM:"); 
-    let value = c_lang::interpret(par.clone());
+    let value = c_lang::interpret(&program);
     println!("{value}");
 
     println!("\n- OUT:");
@@ -0,0 +1,274 @@
+use crate::assembly::{self, AssemblyOptions, SourceLine};
+use crate::cfg::{self, ControlFlowGraph, DecodedInstruction};
+use crate::vc_8bit::Byte;
+
+/// The kinds of problems the analyzer finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// Code that no path from the entry point reaches
+    UnreachableCode,
+    /// A jump to the second byte of a 2 byte instruction
+    JumpIntoInstruction,
+    /// A path that runs past the end of the code without a `HALT`
+    MissingHalt,
+    /// A register that is read when nothing has written it
+    UnwrittenRegister,
+    /// A `STR` to an address that holds code
+    StoreIntoCode,
+    /// A `DIV` by a register that is known to be 0
+    DivideByZero,
+    /// A port address that is not 0 to 7
+    InvalidPort,
+    /// A byte that is run but is not an instruction
+    InvalidInstruction,
+}
+
+/// # Diagnostic
+/// A problem found by the analyzer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    /// The address of the instruction, `None` for problems found in the source before it was assembled
+    pub address: Option<i32>,
+    /// The line of source the problem is on, when the source is known
+    pub source: Option<SourceLine>,
+    pub message: String,
+}
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.source, self.address) {
+            (Some(source), _) => write!(f, "{}: {}", source.location(), self.message),
+            (None, Some(address)) => write!(f, "address {address:02x}: {}", self.message),
+            (None, None) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// # analyze_source
+/// Assembles the code and looks for problems without running it. See `analyze_bytes` for what is found,
+/// along with port addresses that are not 0 to 7. Only lines in the code segment are taken as code.
+/// # Arguments
+/// * `contents` - The assembly code
+/// * `options` - The defines to assemble with
+/// # Returns
+/// * `Vec<Diagnostic>` - The port problems, then the other problems in order of address
+/// # Examples
+/// ```
+/// use vc_8bit::analyzer::{analyze_source, Lint};
+/// use vc_8bit::assembly::AssemblyOptions;
+/// let diagnostics = analyze_source("MOV R0 10\nMOV R1 0\nDIV R0 R1\nRPRT R0 9\nHALT\nOUT R0", &AssemblyOptions::default());
+/// assert_eq!(diagnostics.iter().map(|x| x.lint).collect::<Vec<Lint>>(), vec![Lint::InvalidPort, Lint::DivideByZero, Lint::UnreachableCode]);
+/// assert_eq!(diagnostics[1].to_string(), "line 3: DIV R0 R1 divides by R1 which is always 0 here");
+/// // `P` is 3 like the assembler reads it, the second `%ASSIGN` doesn't change it
+/// assert!(analyze_source("%ASSIGN P 3\n%ASSIGN P 9\nRPRT R0 P\nHALT", &AssemblyOptions::default()).is_empty());
+/// ```
+pub fn analyze_source(contents: &str, options: &AssemblyOptions) -> Vec<Diagnostic> {
+    analyze_lines(assembly::preprocess_with_options(contents, options))
+}
+/// # analyze_file
+/// Reads a file and analyzes it like `analyze_source`
+pub fn analyze_file(path: &str, options: &AssemblyOptions) -> Vec<Diagnostic> {
+    analyze_lines(assembly::preprocess_file_with_options(path, options))
+}
+
+/// Checks the ports, then assembles the lines and analyzes the code
+fn analyze_lines(mut lines: Vec<SourceLine>) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    // the assembler can't encode a port past 7, so they are found and replaced before assembling
+    let mut constants: Vec<(String, i32)> = vec![];
+    for source_line in lines.iter_mut() {
+        let mut line = assembly::tokenize_line(&source_line.text);
+        let first = line.parts.first().map(|x| x.to_uppercase()).unwrap_or_default();
+        if first == "%ASSIGN" && line.parts.len() == 3 {
+            if let Some(value) = assembly::parse_value(&line.parts[2]) {
+                constants.push((line.parts[1].clone(), value));
+            }
+        }
+        if (first == "RPRT" || first == "WPRT") && line.parts.len() == 3 {
+            // like the assembler, the first value given to a name is the one used
+            let port = assembly::parse_value(&line.parts[2]).or_else(|| constants.iter().find(|x| x.0 == line.parts[2]).map(|x| x.1));
+            if let Some(port) = port.filter(|x| !(0..=7).contains(x)) {
+                diagnostics.push(Diagnostic { lint: Lint::InvalidPort, address: None, source: Some(source_line.clone()), message: format!("{first} uses port {port}, ports are 0 to 7") });
+                line.parts[2] = "0".to_string();
+                source_line.text = line.label.map_or(String::new(), |x| format!("{x}: ")) + &line.parts.join(" ");
+            }
+        }
+    }
+
+    let code = cfg::assemble_code(lines);
+    let mut graph = ControlFlowGraph::with_code(&code.bytes, code.entry, &code.is_code);
+    graph.sources = code.sources;
+    diagnostics.extend(analyze_graph(&graph, &code.bytes, &code.is_code, &code.instructions));
+    diagnostics
+}
+
+/// # analyze_bytes
+/// Looks for problems in a program without running it. It finds
+/// * code that can never run
+/// * jumps into the middle of a 2 byte instruction
+/// * paths that run past the end of the program without `HALT`
+/// * registers that are read when nothing has written them, apart from `STR` which is often used to save a register
+/// * `STR` into an address that holds code
+/// * `DIV` by a register that is known to be 0
+/// * bytes that are run but are not instructions
+///
+/// Runs of zero bytes that can't be reached are taken as padding and not reported.
+/// # Arguments
+/// * `bytes` - The program, starting at address 0
+/// * `entry` - The address the program starts running at
+/// # Returns
+/// * `Vec<Diagnostic>` - The problems, in order of address
+/// # Examples
+/// ```
+/// use vc_8bit::analyzer::{analyze_bytes, Lint};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// let bytes = string_to_bytes(&compile_assembly_to_binary("OUT R2\nSTR R0 0\nJMP 1"));
+/// let diagnostics = analyze_bytes(&bytes, 0);
+/// assert_eq!(diagnostics.iter().map(|x| x.lint).collect::<Vec<Lint>>(), vec![Lint::UnwrittenRegister, Lint::StoreIntoCode]);
+/// ```
+pub fn analyze_bytes(bytes: &[Byte], entry: i32) -> Vec<Diagnostic> {
+    let graph = ControlFlowGraph::new(bytes, entry);
+    analyze_graph(&graph, bytes, &vec![true; bytes.len()], &graph.instructions)
+}
+
+/// What is known about the registers at a point in the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    /// A bit for every register that may have been written
+    written: u8,
+    /// The value of every register that has the same value on every path
+    known: [Option<i32>; 4],
+}
+impl State {
+    fn merge(&self, other: &State) -> State {
+        let mut known = self.known;
+        for (i, value) in known.iter_mut().enumerate() {
+            if *value != other.known[i] {
+                *value = None;
+            }
+        }
+        State { written: self.written | other.written, known }
+    }
+    fn run(&mut self, instruction: &DecodedInstruction) {
+        let registers = &instruction.registers;
+        let both = || Some((self.known[registers[0] as usize]?, self.known[registers[1] as usize]?));
+        let value = match instruction.mnemonic {
+            "MOV" => instruction.value,
+            "CPY" => self.known[registers[1] as usize],
+            "SUB" if registers[0] == registers[1] => Some(0),
+            "SUB" => both().map(|(a, b)| (a - b).abs()),
+            "ADD" => both().map(|(a, b)| a + b).filter(|x| *x <= 255),
+            "AND" => both().map(|(a, b)| a & b),
+            "OR" => both().map(|(a, b)| a | b),
+            "XOR" => both().map(|(a, b)| a ^ b),
+            "NOT" => self.known[registers[0] as usize].map(|a| !a & 255),
+            _ => None,
+        };
+        for register in instruction.writes() {
+            self.known[register as usize] = value;
+            self.written |= 1 << register;
+        }
+    }
+}
+
+/// Finds the problems in a graph
+fn analyze_graph(graph: &ControlFlowGraph, bytes: &[Byte], is_code: &[bool], instructions: &[DecodedInstruction]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    let mut report = |lint: Lint, address: i32, message: String| {
+        let source = graph.sources.get(address as usize).cloned().flatten();
+        diagnostics.push(Diagnostic { lint, address: Some(address), source, message });
+    };
+    let mut covered = vec![false; bytes.len()];
+    for instruction in &graph.instructions {
+        for address in instruction.address..(instruction.address + instruction.size).min(bytes.len() as i32) {
+            covered[address as usize] = true;
+        }
+    }
+
+    // code that can't be reached, in runs
+    let mut address = 0;
+    while address < bytes.len() {
+        if !is_code[address] || covered[address] {
+            address += 1;
+            continue;
+        }
+        let start = address;
+        while address < bytes.len() && is_code[address] && !covered[address] {
+            address += 1;
+        }
+        if bytes[start..address].iter().any(|x| x.to_i32() != 0) {
+            report(Lint::UnreachableCode, start as i32, format!("{} byte{} of code at {start:02x} can never run", address - start, if address - start == 1 { "" } else { "s" }));
+        }
+    }
+
+    for instruction in &graph.instructions {
+        if let Some(target) = instruction.jump_target() {
+            if let Some(other) = instructions.iter().find(|x| x.address < target && target < x.address + x.size) {
+                report(Lint::JumpIntoInstruction, instruction.address, format!("{instruction} jumps into the middle of {other} at {:02x}", other.address));
+            }
+        }
+    }
+    for (address, next) in &graph.exits {
+        let instruction = graph.instructions.iter().find(|x| x.address == *address).unwrap();
+        if instruction.jump_target() == Some(*next) {
+            report(Lint::MissingHalt, *address, format!("{instruction} jumps to {next:02x} which is not code"));
+        }
+        else {
+            report(Lint::MissingHalt, *address, format!("{instruction} runs past the end of the code without HALT"));
+        }
+    }
+    for address in &graph.invalid {
+        report(Lint::InvalidInstruction, *address, format!("byte {} at {address:02x} is not an instruction", bytes[*address as usize].to_string()));
+    }
+
+    // find what is known about the registers at the start of every block
+    let mut states: Vec<Option<State>> = vec![None; graph.blocks.len()];
+    let index = |start: i32| graph.blocks.iter().position(|x| x.start == start);
+    if let Some(entry) = index(graph.entry) {
+        states[entry] = Some(State { written: 0, known: [Some(0); 4] });
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, block) in graph.blocks.iter().enumerate() {
+            let mut state = match states[i] {
+                Some(state) => state,
+                None => continue,
+            };
+            block.instructions.iter().for_each(|x| state.run(x));
+            for edge in graph.edges.iter().filter(|x| x.from == block.start) {
+                let next = index(edge.to).unwrap();
+                let merged = states[next].map_or(state, |x| x.merge(&state));
+                if states[next] != Some(merged) {
+                    states[next] = Some(merged);
+                    changed = true;
+                }
+            }
+        }
+    }
+    for (i, block) in graph.blocks.iter().enumerate() {
+        let mut state = match states[i] {
+            Some(state) => state,
+            None => continue,
+        };
+        for instruction in &block.instructions {
+            if instruction.mnemonic != "STR" {
+                for register in instruction.reads().into_iter().filter(|x| state.written & (1 << x) == 0) {
+                    report(Lint::UnwrittenRegister, instruction.address, format!("{instruction} reads R{register} which is never written before it"));
+                }
+            }
+            if instruction.mnemonic == "DIV" && state.known[instruction.registers[1] as usize] == Some(0) {
+                report(Lint::DivideByZero, instruction.address, format!("{instruction} divides by R{} which is always 0 here", instruction.registers[1]));
+            }
+            if let (Some(address), "STR") = (instruction.value, instruction.mnemonic) {
+                if covered.get(address as usize) == Some(&true) {
+                    report(Lint::StoreIntoCode, instruction.address, format!("{instruction} writes over the code at {address:02x}"));
+                }
+            }
+            state.run(instruction);
+        }
+    }
+    diagnostics.sort_by_key(|x| x.address.unwrap_or(-1));
+    diagnostics
+}
@@ -1,5 +1,8 @@
 use crate::vc_8bit::{Byte, Bit};
+use crate::isa::{self, OperandKind};
 use regex::Regex;
+use crate::vcimg::{ImageSymbol, Segment, SegmentKind, VcImage};
+use std::path::{Path, PathBuf};
 
 /// # string_to_bytes
 /// Converts a string to a vector of bytes
@@ -44,6 +47,18 @@ pub fn string_to_bytes(contents: &str) -> Vec<Byte> {
 /// let bytes = compile_assembly_to_binary("MOV R2 255");
 /// assert_eq!(bytes, "1100101011111111");
 /// ```
+/// labels can be used in place of addresses
+/// ```
+/// use vc_8bit::assembly::compile_assembly_to_binary;
+/// let bytes = compile_assembly_to_binary("start: INC R0\nJMP start");
+/// assert_eq!(bytes, "111000001110100000000000");
+/// ```
+/// `%ORG` before any code sets the address the program starts at, after code it fills the space up to the address with zeros
+/// ```
+/// use vc_8bit::assembly::compile_assembly_to_binary;
+/// let bytes = compile_assembly_to_binary("%ORG 1\nJMP end\n%ORG 4\nend: HALT");
+/// assert_eq!(bytes, "0000000011101000000001000000000011111111");
+/// ```
 /// # Panics
 /// This function will panic if the assembly code is invalid
 /// ```should_panic
@@ -52,215 +67,1152 @@ pub fn string_to_bytes(contents: &str) -> Vec<Byte> {
 /// let bytes = compile_assembly_to_binary("BLAH 256");
 /// ```
 pub fn compile_assembly_to_binary(contents: &str) -> String {
-    let mut out = String::new();
-    let mut vars: Vec<(String, Byte)> = vec![];
-    let lines = contents.split("\n").map(|x| x.trim()).filter(|x| *x != "").collect::<Vec<&str>>();
-    for line in lines {
-        let mut parts = line.split(" ").map(|x| x.trim()).filter(|x: &&str| *x != "").collect::<Vec<&str>>().into_iter().peekable();
-        let mut stream = String::new();
-        while let Some(part) = parts.peek() {
-            if part.starts_with(";") {
-                break;
-            }
-            match part.to_uppercase().as_str() {
-                "%ASSIGN" => {
-                    parts.next();
-                    let name = parts.next().unwrap();
-                    let value = get_binary(parts.next().unwrap(), &vars).chars().collect::<Vec<char>>();
-                    vars.push((name.to_string(), Byte::new([Bit::new(value[0] == '1'), Bit::new(value[1] == '1'), Bit::new(value[2] == '1'), Bit::new(value[3] == '1'), Bit::new(value[4] == '1'), Bit::new(value[5] == '1'), Bit::new(value[6] == '1'), Bit::new(value[7] == '1')])));
-                    break;
-                }
-                "ADD" => {
-                    stream += "0000";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
-                }
-                "SUB" => {
-                    stream += "0001";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
-                }
-                "MUL" => {
-                    stream += "0010";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
-                }
-                "DIV" => {
-                    stream += "0011";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
-                }
-                "STR" => {
-                    stream += "110000";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "LDR" => {
-                    stream += "110001";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "MOV" => {
-                    stream += "110010";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "CPY" => {
-                    stream += "110011";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
-                    stream += "000000";
-                }
-                "SHL" => {
-                    stream += "110100";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "SHR" => {
-                    stream += "110101";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "OUT" => {
-                    stream += "110110";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                }
-                "MSG" => {
-                    stream += "110111";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                }
-                "INC" => {
-                    stream += "111000";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                }
-                "DEC" => {
-                    stream += "111001";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                }
-                "JMP" => {
-                    stream += "11101000";
-                    parts.next();
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
-                }
-                "JMP_NEG" => {
-                    stream += "11101001";
-                    parts.next();
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
+    assemble(preprocess(contents)).binary()
+}
+/// # compile_assembly_file_to_binary
+/// Compiles an assembly file to binary. Files it includes are found relative to it and errors name the file they came from.
+/// # Arguments
+/// * `path` - The path to the assembly file
+/// # Returns
+/// * `String` - The binary code
+/// # Panics
+/// This function will panic if a file can't be read or the assembly code is invalid
+pub fn compile_assembly_file_to_binary(path: &str) -> String {
+    assemble(preprocess_file(path)).binary()
+}
+/// # compile_assembly_to_binary_with_options
+/// Compiles assembly code to binary with names defined from outside the code, so one source can build different versions
+/// # Arguments
+/// * `contents` - The assembly code to compile
+/// * `options` - The assembler options
+/// # Returns
+/// * `String` - The binary code
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::{compile_assembly_to_binary_with_options, AssemblyOptions};
+/// let code = "%ASSIGN TRACE 0\n%IF TRACE > 0\nOUT R0\n%ENDIF\nHALT";
+/// let mut options = AssemblyOptions::default();
+/// assert_eq!(compile_assembly_to_binary_with_options(code, &options), "11111111");
+/// options.add_define("TRACE=1");
+/// assert_eq!(compile_assembly_to_binary_with_options(code, &options), "1101100011111111");
+/// ```
+/// # Panics
+/// This function will panic if the assembly code is invalid
+pub fn compile_assembly_to_binary_with_options(contents: &str, options: &AssemblyOptions) -> String {
+    assemble(preprocess_with_options(contents, options)).binary()
+}
+/// # compile_assembly_file_to_binary_with_options
+/// Compiles an assembly file to binary with names defined from outside the code
+/// # Arguments
+/// * `path` - The path to the assembly file
+/// * `options` - The assembler options
+/// # Returns
+/// * `String` - The binary code
+/// # Panics
+/// This function will panic if a file can't be read or the assembly code is invalid
+pub fn compile_assembly_file_to_binary_with_options(path: &str, options: &AssemblyOptions) -> String {
+    assemble(preprocess_file_with_options(path, options)).binary()
+}
+/// # assemble_program
+/// Assembles code and keeps the address and binary of every line along with the symbols, which is what listings are made from
+/// # Arguments
+/// * `contents` - The assembly code
+/// * `options` - The assembler options
+/// # Returns
+/// * `AssembledProgram` - The assembled lines and symbols
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+/// let program = assemble_program("%ASSIGN FIVE 5\nstart: MOV R0 FIVE\nJMP start", &AssemblyOptions::default());
+/// assert_eq!(program.lines[2].address, 2);
+/// assert_eq!(program.binary(), "11001000000001011110100000000000");
+/// assert!(program.listing().contains("02    e8 00  11101000 00000000      3  JMP start"));
+/// ```
+/// # Panics
+/// This function will panic if the assembly code is invalid
+pub fn assemble_program(contents: &str, options: &AssemblyOptions) -> AssembledProgram {
+    assemble(preprocess_with_options(contents, options))
+}
+/// # assemble_program_file
+/// Assembles a file and keeps the address and binary of every line along with the symbols
+/// # Arguments
+/// * `path` - The path to the assembly file
+/// * `options` - The assembler options
+/// # Returns
+/// * `AssembledProgram` - The assembled lines and symbols
+/// # Panics
+/// This function will panic if a file can't be read or the assembly code is invalid
+pub fn assemble_program_file(path: &str, options: &AssemblyOptions) -> AssembledProgram {
+    assemble(preprocess_file_with_options(path, options))
+}
+
+/// A line of source after it has been assembled
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledLine {
+    pub source: SourceLine,
+    /// The address of the first byte of the line
+    pub address: i32,
+    /// The binary of the line as '0' and '1' characters, empty for lines that don't take memory
+    pub binary: String,
+    /// The real instructions and their binary when the line is a pseudo instruction
+    pub expansion: Vec<(String, String)>,
+    /// The segment the line is in, set with `%SEGMENT`
+    pub segment: SegmentKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// An address defined with `name:`
+    Label,
+    /// A value defined with `%ASSIGN`
+    Constant,
+    /// A name from another module brought in with `%EXTERN`, its value is set when the modules are linked
+    Extern,
+}
+
+/// A name defined in assembly code and the value it resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: Byte,
+    pub kind: SymbolKind,
+    /// If the symbol was exported with `%GLOBAL` so other modules can use it
+    pub global: bool,
+    /// Where the symbol was defined
+    pub source: SourceLine,
+}
+
+/// The result of assembling a program, with every line and symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledProgram {
+    pub lines: Vec<AssembledLine>,
+    pub symbols: Vec<Symbol>,
+    /// The address the program starts at, set with an `%ORG` before any code, `None` if the program can be placed anywhere by the linker
+    pub origin: Option<i32>,
+}
+impl AssembledProgram {
+    /// returns the binary of the whole program as '0' and '1' characters, starting at address 0 with zeros before the origin
+    /// # Panics
+    /// Will panic if the program uses `%EXTERN` names, those programs have to be linked with `linker::link`
+    pub fn binary(&self) -> String {
+        if let Some(symbol) = self.symbols.iter().find(|x| x.kind == SymbolKind::Extern) {
+            panic!("'{}' is %EXTERN and needs to be linked in {}", symbol.name, symbol.source.location());
+        }
+        "00000000".repeat(self.origin.unwrap_or(0) as usize) + &self.code()
+    }
+    /// returns the binary of the program starting at the origin
+    pub fn code(&self) -> String {
+        self.lines.iter().map(|x| x.binary.as_str()).collect()
+    }
+    /// returns the program as bytes
+    pub fn bytes(&self) -> Vec<Byte> {
+        string_to_bytes(&self.binary())
+    }
+    /// # symbols_json
+    /// Writes the labels, constants and `%BYTE` data regions as JSON for debuggers and other tools
+    /// ```text
+    /// {
+    ///   "version": 1,
+    ///   "symbols": [
+    ///     { "name": "start", "kind": "label", "value": 4, "global": false, "file": "main.asm", "line": 3 }
+    ///   ],
+    ///   "regions": [
+    ///     { "label": "message", "segment": "rodata", "address": 0, "size": 4, "file": "main.asm", "line": 2 }
+    ///   ]
+    /// }
+    /// ```
+    /// `kind` is `label`, `constant` or `extern`, `segment` is `code`, `data` or `rodata` and `label` is `null` for a `%BYTE` line without one.
+    /// `file` is empty when the source was not read from a file.
+    /// # Examples
+    /// ```
+    /// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+    /// let program = assemble_program("%ASSIGN FIVE 5\nvalue: %BYTE FIVE\nstart: HALT", &AssemblyOptions::default());
+    /// let json = program.symbols_json();
+    /// assert!(json.contains(r#"{ "name": "start", "kind": "label", "value": 1, "global": false, "file": "", "line": 3 }"#));
+    /// assert!(json.contains(r#"{ "label": "value", "segment": "code", "address": 0, "size": 1, "file": "", "line": 2 }"#));
+    /// ```
+    pub fn symbols_json(&self) -> String {
+        let symbols = self.symbols.iter().map(|x| {
+            let kind = match x.kind {
+                SymbolKind::Label => "label",
+                SymbolKind::Constant => "constant",
+                SymbolKind::Extern => "extern",
+            };
+            format!("    {{ \"name\": {}, \"kind\": \"{kind}\", \"value\": {}, \"global\": {}, \"file\": {}, \"line\": {} }}", json_string(&x.name), x.value.to_i32(), x.global, json_string(&x.source.file), x.source.line)
+        }).collect::<Vec<String>>();
+        let regions = self.lines.iter().filter(|x| tokenize_line(&x.source.text).parts.first().is_some_and(|x| x.to_uppercase() == "%BYTE")).map(|x| {
+            let label = tokenize_line(&x.source.text).label.map_or("null".to_string(), |x| json_string(&x));
+            let segment = match x.segment {
+                SegmentKind::Code => "code",
+                SegmentKind::Data => "data",
+                SegmentKind::ReadOnly => "rodata",
+            };
+            format!("    {{ \"label\": {label}, \"segment\": \"{segment}\", \"address\": {}, \"size\": {}, \"file\": {}, \"line\": {} }}", x.address, x.binary.len() / 8, json_string(&x.source.file), x.source.line)
+        }).collect::<Vec<String>>();
+        format!("{{\n  \"version\": 1,\n  \"symbols\": {},\n  \"regions\": {}\n}}\n", json_list(&symbols), json_list(&regions))
+    }
+    /// # to_vcimg
+    /// Packs the program in a `.vcimg` container. Lines next to each other in the same `%SEGMENT` become one segment
+    /// and the program starts running at the first code segment.
+    /// # Arguments
+    /// * `with_symbols` - If the labels and constants are put in the image for debuggers
+    /// # Examples
+    /// ```
+    /// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+    /// use vc_8bit::vcimg::SegmentKind;
+    /// let program = assemble_program("%SEGMENT RODATA\ntext: %BYTE \"hi\" 0\n%SEGMENT CODE\nstart: HALT", &AssemblyOptions::default());
+    /// let image = program.to_vcimg(true);
+    /// assert_eq!(image.segments.iter().map(|x| (x.kind, x.address, x.bytes.len())).collect::<Vec<_>>(), vec![(SegmentKind::ReadOnly, 0, 3), (SegmentKind::Code, 3, 1)]);
+    /// assert_eq!(image.entry, 3);
+    /// ```
+    /// # Panics
+    /// Will panic if the program uses `%EXTERN` names
+    pub fn to_vcimg(&self, with_symbols: bool) -> VcImage {
+        if let Some(symbol) = self.symbols.iter().find(|x| x.kind == SymbolKind::Extern) {
+            panic!("'{}' is %EXTERN and needs to be linked in {}", symbol.name, symbol.source.location());
+        }
+        let mut segments: Vec<Segment> = vec![];
+        for line in self.lines.iter().filter(|x| !x.binary.is_empty()) {
+            match segments.last_mut() {
+                Some(segment) if segment.kind == line.segment && segment.address + segment.bytes.len() as i32 == line.address => segment.bytes.extend(string_to_bytes(&line.binary)),
+                _ => segments.push(Segment { kind: line.segment, address: line.address, bytes: string_to_bytes(&line.binary) }),
+            }
+        }
+        let load_address = segments.iter().map(|x| x.address).min().unwrap_or(self.origin.unwrap_or(0));
+        let entry = segments.iter().find(|x| x.kind == SegmentKind::Code).map_or(load_address, |x| x.address);
+        let symbols = self.symbols.iter().map(|x| ImageSymbol { name: x.name.clone(), value: x.value.to_i32(), is_label: x.kind == SymbolKind::Label }).collect();
+        VcImage { isa_revision: crate::vc_8bit::ISA_REVISION, entry, load_address, segments, symbols: if with_symbols { Some(symbols) } else { None } }
+    }
+    /// # listing
+    /// Creates a listing of the program. Each source line is shown with its address and the bytes it assembled to
+    /// in hex and binary, followed by a table of the labels and constants with their values.
+    pub fn listing(&self) -> String {
+        let mut out = format!("{:<4}  {:<5}  {:<17}  {:>5}  {}\n", "ADDR", "HEX", "BINARY", "LINE", "SOURCE");
+        let mut file: Option<&str> = None;
+        for line in &self.lines {
+            // lines from included files are shown under the name of the file
+            if file != Some(line.source.file.as_str()) && line.source.line != 0 {
+                file = Some(line.source.file.as_str());
+                if !line.source.file.is_empty() {
+                    out += format!("{:39}; {}\n", "", line.source.file).as_str();
                 }
-                "JMP_ZRO" => {
-                    stream += "11101010";
-                    parts.next();
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
+            }
+            let bytes = string_to_bytes(&line.binary);
+            let address = if bytes.is_empty() { "  ".to_string() } else { format!("{:02x}", line.address) };
+            let (hex, binary) = if bytes.len() > 2 && line.expansion.is_empty() && tokenize_line(&line.source.text).parts.first().is_some_and(|x| x.to_uppercase().ends_with("ORG")) {
+                // the padding from an %ORG is too long to show byte by byte
+                ("00 ..".to_string(), format!("{} zero bytes", bytes.len()))
+            }
+            else {
+                (bytes.iter().map(|x| x.to_hex()).collect::<Vec<String>>().join(" "), bytes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" "))
+            };
+            let number = if line.source.line == 0 { line.source.file.clone() } else { line.source.line.to_string() };
+            if line.expansion.is_empty() {
+                out += format!("{address:<4}  {hex:<5}  {binary:<17}  {number:>5}  {}", line.source.text.trim()).trim_end();
+                out += "\n";
+                continue;
+            }
+
+            // pseudo instructions are shown with the instructions they expand to under them
+            out += format!("{address:<4}  {:<5}  {:<17}  {number:>5}  {}", "", "", line.source.text.trim()).trim_end();
+            out += "\n";
+            let mut instruction_address = line.address;
+            for (instruction, instruction_binary) in &line.expansion {
+                let bytes = string_to_bytes(instruction_binary);
+                let hex = bytes.iter().map(|x| x.to_hex()).collect::<Vec<String>>().join(" ");
+                let binary = bytes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ");
+                out += format!("{instruction_address:02x}    {hex:<5}  {binary:<17}  {:>5}    {instruction}\n", "+").as_str();
+                instruction_address += bytes.len() as i32;
+            }
+        }
+
+        out += "\nSYMBOL                VALUE  HEX  BINARY    KIND\n";
+        for symbol in &self.symbols {
+            let kind = match symbol.kind {
+                SymbolKind::Label => "label",
+                SymbolKind::Constant => "constant",
+                SymbolKind::Extern => "extern",
+            };
+            let kind = if symbol.global { format!("{kind} global") } else { kind.to_string() };
+            out += format!("{:<20}  {:>5}  {}   {}  {kind}\n", symbol.name, symbol.value.to_i32(), symbol.value.to_hex(), symbol.value.to_string()).as_str();
+        }
+        out
+    }
+}
+pub(crate) fn assemble(lines: Vec<SourceLine>) -> AssembledProgram {
+    let mut out: Vec<AssembledLine> = vec![];
+    let mut symbols: Vec<Symbol> = get_labels(&lines);
+    let mut vars: Vec<(String, Byte)> = symbols.iter().map(|x| (x.name.clone(), x.value)).collect();
+    let mut globals: Vec<(String, SourceLine)> = vec![];
+    let mut address = 0;
+    let mut origin = None;
+    let mut segment = SegmentKind::Code;
+    for source_line in lines {
+        let line = tokenize_line(&source_line.text);
+        let parts = line.parts.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+        let mut stream = String::new();
+        let mut expansion: Vec<(String, String)> = vec![];
+        match parts.first() {
+            Some(part) if part.to_uppercase() == "%ORG" || part.to_uppercase() == ".ORG" => {
+                let target = Byte::from_string(get_binary(parts.get(1).unwrap_or(&""), &vars)).to_i32();
+                if out.iter().all(|x| x.binary.is_empty()) {
+                    // before any code the %ORG says where the program starts
+                    origin = Some(target);
+                    address = target;
                 }
-                "JMP_ABV" => {
-                    stream += "11101011";
-                    parts.next();
-                    stream += get_binary(parts.next().unwrap(), &vars).as_str();
+                else {
+                    // after code the space up to the address is filled with zeros
+                    stream += &"00000000".repeat((target - address) as usize);
                 }
-                "CMP_NEG" => {
-                    stream += "111100";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
+            }
+            Some(part) if part.to_uppercase() == "%GLOBAL" => {
+                globals.extend(parts[1..].iter().map(|x| (x.to_string(), source_line.clone())));
+            }
+            Some(part) if part.to_uppercase() == "%EXTERN" => {}
+            Some(part) if part.to_uppercase() == "%SEGMENT" => {
+                segment = match parts.get(1).and_then(|x| SegmentKind::from_name(x)) {
+                    Some(kind) => kind,
+                    None => panic!("Expected %SEGMENT CODE, DATA or RODATA in {}", source_line.location()),
+                };
+            }
+            Some(part) if part.to_uppercase() == "%BYTE" => {
+                for value in data_values(&parts[1..], &source_line) {
+                    stream += &get_binary(&value, &vars);
                 }
-                "CMP_ZRO" => {
-                    stream += "111101";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
+            }
+            Some(part) if part.to_uppercase() == "%ASSIGN" => {
+                if parts.len() != 3 {
+                    panic!("Expected %ASSIGN NAME value in {}", source_line.location());
                 }
-                "CMP_ABV" => {
-                    stream += "111110";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
+                let name = parts[1];
+                let value = get_binary(parts[2], &vars).chars().collect::<Vec<char>>();
+                vars.push((name.to_string(), Byte::new([Bit::new(value[0] == '1'), Bit::new(value[1] == '1'), Bit::new(value[2] == '1'), Bit::new(value[3] == '1'), Bit::new(value[4] == '1'), Bit::new(value[5] == '1'), Bit::new(value[6] == '1'), Bit::new(value[7] == '1')])));
+                symbols.push(Symbol { name: name.to_string(), value: vars.last().unwrap().1, kind: SymbolKind::Constant, global: false, source: source_line.clone() });
+            }
+            Some(_) => {
+                let scratch = if vars.iter().any(|x| x.0 == "SCRATCH") { "SCRATCH" } else { SCRATCH_ADDRESS };
+                match expand_pseudo_instruction(&parts, scratch) {
+                    Some(instructions) => {
+                        for instruction in instructions {
+                            let instruction_parts = tokenize_line(&instruction).parts;
+                            let binary = encode_instruction(&instruction_parts.iter().map(|x| x.as_str()).collect::<Vec<&str>>(), &vars, &source_line);
+                            stream += &binary;
+                            expansion.push((instruction, binary));
+                        }
+                    }
+                    None => stream += &encode_instruction(&parts, &vars, &source_line),
                 }
-                "HALT" => {
-                    stream += "11111111";
-                    parts.next();
+            }
+            None => {}
+        }
+        let length = stream.len() as i32 / 8;
+        out.push(AssembledLine { source: source_line, address, binary: stream, expansion, segment });
+        address += length;
+    }
+    for (name, source_line) in globals {
+        match symbols.iter_mut().find(|x| x.name == name && x.kind != SymbolKind::Extern) {
+            Some(symbol) => symbol.global = true,
+            None => panic!("%GLOBAL '{name}' is not defined in {}", source_line.location()),
+        }
+    }
+    AssembledProgram { lines: out, symbols, origin }
+}
+/// Quotes a string for JSON
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out + "\""
+}
+/// Puts JSON values that are already indented in a list, one per line
+pub(crate) fn json_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return "[]".to_string();
+    }
+    format!("[\n{}\n  ]", items.join(",\n"))
+}
+/// Gets the values of a `%BYTE` line, a string in double quotes is one value per character
+pub(crate) fn data_values(operands: &[&str], source_line: &SourceLine) -> Vec<String> {
+    let mut values: Vec<String> = vec![];
+    for operand in operands {
+        let text = match operand.strip_prefix('"') {
+            Some(text) => text.strip_suffix('"').unwrap_or_else(|| panic!("Missing closing '\"' in {}", source_line.location())),
+            None => {
+                values.push(operand.to_string());
+                continue;
+            }
+        };
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '\\' => match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some(c) => c,
+                    None => panic!("Missing character after '\\' in {}", source_line.location()),
+                },
+                c => c,
+            };
+            values.push((c as u8).to_string());
+        }
+    }
+    values
+}
+/// Encodes a single instruction into binary, using the definition from `isa::INSTRUCTIONS`
+fn encode_instruction(parts: &[&str], vars: &[(String, Byte)], source_line: &SourceLine) -> String {
+    let part = match parts.first() {
+        Some(part) => *part,
+        None => return String::new(),
+    };
+    let definition = match isa::lookup(part) {
+        Some(definition) => definition,
+        None => panic!("Invalid function call {part} in {}", source_line.location()),
+    };
+    if let Some(extra) = parts.get(definition.operands.len() + 1) {
+        panic!("Unexpected '{extra}' after instruction in {}", source_line.location());
+    }
+    let operands = definition.operands.iter().enumerate().map(|(i, field)| {
+        let operand = match parts.get(i + 1) {
+            Some(operand) => *operand,
+            None => panic!("{part} expects {} operands in {}", definition.operands.len(), source_line.location()),
+        };
+        match field.kind {
+            OperandKind::Register => i32::from_str_radix(get_register(operand), 2).unwrap(),
+            OperandKind::Value => i32::from_str_radix(&get_binary(operand, vars), 2).unwrap(),
+            OperandKind::Port => {
+                let address = get_binary(operand, vars);
+                match i32::from_str_radix(&address, 2).unwrap() {
+                    port @ 0..=7 => port,
+                    _ => panic!("Invalid port address {address}. Must be 0-7"),
                 }
-                "AND" => {
-                    stream += "0100";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
+            }
+        }
+    }).collect::<Vec<i32>>();
+    definition.encode(&operands).iter().map(|x| format!("{x:08b}")).collect()
+}
+
+/// The address `SWAP`, `CMP` and `TST` use to save a register, unless the code assigns a different `SCRATCH` address.
+/// The C compiler puts variables from 254 down, so 255 is left free.
+pub const SCRATCH_ADDRESS: &str = "#11111111";
+
+/// # expand_pseudo_instruction
+/// Gets the real instructions a pseudo instruction is made of
+/// * `NOP` - `AND R0 R0`, does nothing in 1 byte
+/// * `CLR Rn` - `MOV Rn 0`
+/// * `TST Rn` - sets the zero flag if the register is 0 without changing it
+/// * `JEQ addr` - `JMP_ZRO addr`
+/// * `JNE addr` - `JMP_NEG addr` then `JMP_ABV addr`
+/// * `SWAP Ra Rb` - swaps 2 registers using the scratch address
+/// * `CMP Ra Rb` - sets the flags for `Ra - Rb` without changing `Ra`
+/// # Arguments
+/// * `parts` - The instruction and its operands
+/// * `scratch` - The address used to save registers
+/// # Returns
+/// * `Option<Vec<String>>` - The real instructions, or `None` if it isn't a pseudo instruction
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::expand_pseudo_instruction;
+/// let expanded = expand_pseudo_instruction(&["SWAP", "R0", "R1"], "255").unwrap();
+/// assert_eq!(expanded, vec!["STR R0 255", "CPY R0 R1", "LDR R1 255"]);
+/// assert_eq!(expand_pseudo_instruction(&["MOV", "R0", "1"], "255"), None);
+/// ```
+pub fn expand_pseudo_instruction(parts: &[&str], scratch: &str) -> Option<Vec<String>> {
+    let operand = |i: usize| -> &str { parts.get(i).copied().unwrap_or("") };
+    let instructions = match parts.first()?.to_uppercase().as_str() {
+        "NOP" => vec!["AND R0 R0".to_string()],
+        "CLR" => vec![format!("MOV {} 0", operand(1))],
+        "TST" => {
+            // adding 0 sets the flags, the 0 goes in a register saved to the scratch address
+            let register = operand(1);
+            let helper = match get_register(register) {
+                "00" => "R1",
+                "01" => "R2",
+                "10" => "R3",
+                _ => "R0",
+            };
+            vec![format!("STR {helper} {scratch}"), format!("MOV {helper} 0"), format!("ADD {register} {helper}"), format!("LDR {helper} {scratch}")]
+        }
+        "JEQ" => vec![format!("JMP_ZRO {}", operand(1))],
+        "JNE" => vec![format!("JMP_NEG {}", operand(1)), format!("JMP_ABV {}", operand(1))],
+        "SWAP" => vec![format!("STR {} {scratch}", operand(1)), format!("CPY {} {}", operand(1), operand(2)), format!("LDR {} {scratch}", operand(2))],
+        "CMP" => vec![format!("STR {} {scratch}", operand(1)), format!("SUB {} {}", operand(1), operand(2)), format!("LDR {} {scratch}", operand(1))],
+        _ => return None,
+    };
+    Some(instructions)
+}
+/// The most macro expansions that can be nested inside each other before the assembler gives up
+pub const MACRO_RECURSION_LIMIT: usize = 16;
+
+/// A line of assembly source along with the file and line number it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    pub text: String,
+    /// The file the line came from, empty when the code wasn't read from a file
+    pub file: String,
+    pub line: usize,
+}
+impl SourceLine {
+    /// returns where the line came from for error messages, like `line 3` or `lib.asm line 3`
+    pub fn location(&self) -> String {
+        if self.line == 0 {
+            self.file.clone()
+        }
+        else if self.file.is_empty() {
+            format!("line {}", self.line)
+        }
+        else {
+            format!("{} line {}", self.file, self.line)
+        }
+    }
+}
+
+/// A line of assembly split into its label, instruction parts, and comment
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AsmLine {
+    /// The label defined at the start of the line without the `:`
+    pub label: Option<String>,
+    /// The instruction or directive followed by its operands
+    pub parts: Vec<String>,
+    /// The comment at the end of the line including the `;`
+    pub comment: Option<String>,
+}
+
+/// A macro defined with `%MACRO name args` and `%ENDMACRO`
+#[derive(Debug, Clone)]
+struct Macro {
+    name: String,
+    params: Vec<String>,
+    body: Vec<String>,
+}
+
+/// # tokenize_line
+/// Splits a line of assembly into a label, the instruction parts, and a comment.
+/// Quoted operands like `' '` and `';'` are kept as one part.
+/// # Arguments
+/// * `line` - The line of assembly
+/// # Returns
+/// * `AsmLine` - The parts of the line
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::tokenize_line;
+/// let line = tokenize_line("loop: MOV R0 ';' ; load a semicolon");
+/// assert_eq!(line.label, Some("loop".to_string()));
+/// assert_eq!(line.parts, vec!["MOV", "R0", "';'"]);
+/// assert_eq!(line.comment, Some("; load a semicolon".to_string()));
+/// ```
+pub fn tokenize_line(line: &str) -> AsmLine {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut result = AsmLine::default();
+    let mut words: Vec<String> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == ';' {
+            result.comment = Some(chars[i..].iter().collect::<String>().trim_end().to_string());
+            break;
+        }
+        let start = i;
+        if c == '\'' || c == '"' {
+            // quoted value, keep everything up to the closing quote
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
                 }
-                "OR" => {
-                    stream += "0101";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
+                i += 1;
+            }
+            i = (i + 1).min(chars.len());
+        }
+        else {
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ';' {
+                i += 1;
+            }
+        }
+        words.push(chars[start..i].iter().collect());
+    }
+    if words.first().is_some_and(|x| x.len() > 1 && x.ends_with(':')) {
+        let label = words.remove(0);
+        result.label = Some(label.trim_end_matches(':').to_string());
+    }
+    result.parts = words;
+    result
+}
+
+/// # preprocess
+/// Runs the preprocessor over assembly code. Macros defined with `%MACRO` are expanded where they are used
+/// and `%INCLUDE "path.asm"` is replaced with the lines of that file.
+/// # Arguments
+/// * `contents` - The assembly code
+/// # Returns
+/// * `Vec<SourceLine>` - The lines left after preprocessing with the line number they came from
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::preprocess;
+/// let lines = preprocess("%MACRO CLEAR reg\nMOV reg 0\n%ENDMACRO\nCLEAR R1");
+/// assert_eq!(lines[0].text, "MOV R1 0");
+/// assert_eq!(lines[0].line, 4);
+/// ```
+/// local labels start with `%%` and are unique for every expansion
+/// ```
+/// use vc_8bit::assembly::preprocess;
+/// let lines = preprocess("%MACRO SKIP\nJMP %%end\n%%end:\n%ENDMACRO\nSKIP\nSKIP");
+/// assert_eq!(lines[0].text, "JMP __end_1");
+/// assert_eq!(lines[2].text, "JMP __end_2");
+/// ```
+/// # Panics
+/// Will panic if a macro is not closed, is used with the wrong number of arguments, or goes past `MACRO_RECURSION_LIMIT`.
+/// Will also panic if an included file can't be read or includes itself.
+pub fn preprocess(contents: &str) -> Vec<SourceLine> {
+    preprocess_with_options(contents, &AssemblyOptions::default())
+}
+/// # preprocess_with_options
+/// Runs the preprocessor over assembly code with names defined from outside the code.
+/// `%IF`, `%IFDEF` and `%IFNDEF` keep or drop lines depending on the defined names.
+/// # Arguments
+/// * `contents` - The assembly code
+/// * `options` - The assembler options holding the defined names
+/// # Returns
+/// * `Vec<SourceLine>` - The lines left after preprocessing
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::{preprocess_with_options, AssemblyOptions};
+/// let mut options = AssemblyOptions::default();
+/// options.add_define("DEBUG");
+/// let lines = preprocess_with_options("%IFDEF DEBUG\nOUT R0\n%ELSE\nHALT\n%ENDIF", &options);
+/// assert_eq!(lines.last().unwrap().text, "OUT R0");
+/// ```
+/// # Panics
+/// Will panic if a define is not a number between 0 and 255, or for the same reasons as `preprocess`
+/// ```should_panic
+/// use vc_8bit::assembly::{preprocess_with_options, AssemblyOptions};
+/// let mut options = AssemblyOptions::default();
+/// options.add_define("X=300");
+/// preprocess_with_options("HALT", &options); // Define 'X' has the value 300 which is not between 0 and 255
+/// ```
+pub fn preprocess_with_options(contents: &str, options: &AssemblyOptions) -> Vec<SourceLine> {
+    let mut preprocessor = Preprocessor::new(options);
+    let mut lines = preprocessor.define_lines();
+    lines.extend(preprocessor.run(contents, ""));
+    lines
+}
+
+/// # preprocess_file
+/// Runs the preprocessor over an assembly file. Included files are found relative to the file including them.
+/// # Arguments
+/// * `path` - The path to the assembly file
+/// # Returns
+/// * `Vec<SourceLine>` - The lines left after preprocessing with the file and line number they came from
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::preprocess_file;
+/// let dir = std::env::temp_dir().join("vc_8bit_preprocess_file");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("main.asm"), "%INCLUDE \"lib.asm\"\nHALT").unwrap();
+/// std::fs::write(dir.join("lib.asm"), "OUT R0").unwrap();
+/// let lines = preprocess_file(dir.join("main.asm").to_str().unwrap());
+/// assert_eq!(lines.iter().map(|x| x.text.as_str()).collect::<Vec<&str>>(), vec!["OUT R0", "HALT"]);
+/// assert!(lines[0].file.ends_with("lib.asm"));
+///
+/// // a.asm includes b.asm, which includes a.asm again
+/// std::fs::write(dir.join("a.asm"), "%INCLUDE \"b.asm\"").unwrap();
+/// std::fs::write(dir.join("b.asm"), "%INCLUDE \"a.asm\"").unwrap();
+/// let error = std::panic::catch_unwind(|| preprocess_file(dir.join("a.asm").to_str().unwrap())).unwrap_err();
+/// assert!(error.downcast_ref::<String>().unwrap().contains("includes itself"));
+/// ```
+/// # Panics
+/// Will panic if the file can't be read or the preprocessor fails
+pub fn preprocess_file(path: &str) -> Vec<SourceLine> {
+    preprocess_file_with_options(path, &AssemblyOptions::default())
+}
+/// # preprocess_file_with_options
+/// Runs the preprocessor over an assembly file with names defined from outside the code
+/// # Arguments
+/// * `path` - The path to the assembly file
+/// * `options` - The assembler options holding the defined names
+/// # Returns
+/// * `Vec<SourceLine>` - The lines left after preprocessing with the file and line number they came from
+/// # Panics
+/// Will panic if the file can't be read or the preprocessor fails
+pub fn preprocess_file_with_options(path: &str, options: &AssemblyOptions) -> Vec<SourceLine> {
+    let mut preprocessor = Preprocessor::new(options);
+    preprocessor.includes.push(canonical_path(Path::new(path)));
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => panic!("Could not read file '{path}': {e}"),
+    };
+    let mut lines = preprocessor.define_lines();
+    lines.extend(preprocessor.run(&contents, path));
+    lines
+}
+
+/// # AssemblyOptions
+/// Settings for the assembler that come from outside the code, like `-D NAME=value` on the command line
+#[derive(Debug, Clone, Default)]
+pub struct AssemblyOptions {
+    /// Names and values that can be checked with `%IF` and `%IFDEF`, and used as constants like `%ASSIGN`
+    pub defines: Vec<(String, String)>,
+}
+impl AssemblyOptions {
+    /// # add_define
+    /// Adds a define written as `NAME=value` or `NAME`, which gives it the value 1
+    /// # Examples
+    /// ```
+    /// use vc_8bit::assembly::AssemblyOptions;
+    /// let mut options = AssemblyOptions::default();
+    /// options.add_define("PORT=7");
+    /// options.add_define("DEBUG");
+    /// assert_eq!(options.defines, vec![("PORT".to_string(), "7".to_string()), ("DEBUG".to_string(), "1".to_string())]);
+    /// ```
+    pub fn add_define(&mut self, define: &str) {
+        match define.split_once('=') {
+            Some((name, value)) => self.defines.push((name.trim().to_string(), value.trim().to_string())),
+            None => self.defines.push((define.trim().to_string(), "1".to_string())),
+        }
+    }
+}
+
+/// Keeps track of macros, defined names and included files while preprocessing
+struct Preprocessor {
+    macros: Vec<Macro>,
+    expansions: usize,
+    /// The files currently being included, used to find include cycles
+    includes: Vec<PathBuf>,
+    /// Names with a value that `%IF` and `%IFDEF` can check
+    defines: Vec<(String, i32)>,
+    /// How many of the defines came from the options and not from `%ASSIGN`
+    option_defines: usize,
+}
+
+/// An `%IF` that hasn't reached its `%ENDIF` yet
+struct Condition {
+    active: bool,
+    parent_active: bool,
+    has_else: bool,
+    source_line: SourceLine,
+}
+
+impl Preprocessor {
+    fn new(options: &AssemblyOptions) -> Self {
+        let mut defines: Vec<(String, i32)> = vec![];
+        for (name, value) in &options.defines {
+            match parse_value(value) {
+                Some(value) if (0..=255).contains(&value) => defines.push((name.clone(), value)),
+                Some(value) => panic!("Define '{name}' has the value {value} which is not between 0 and 255"),
+                None => panic!("Define '{name}' has the value '{value}' which is not a number"),
+            }
+        }
+        let option_defines = defines.len();
+        Preprocessor { macros: vec![], expansions: 0, includes: vec![], defines, option_defines }
+    }
+    /// Turns the defines from the options into `%ASSIGN` lines so the code can use them as constants
+    fn define_lines(&self) -> Vec<SourceLine> {
+        self.defines[..self.option_defines].iter()
+            .map(|(name, value)| SourceLine { text: format!("%ASSIGN {name} {value}"), file: "-D".to_string(), line: 0 })
+            .collect()
+    }
+    fn run(&mut self, contents: &str, file: &str) -> Vec<SourceLine> {
+        let lines = contents.lines().enumerate()
+            .map(|(i, x)| SourceLine { text: x.to_string(), file: file.to_string(), line: i + 1 })
+            .collect::<Vec<SourceLine>>();
+        self.expand(lines, 0)
+    }
+    fn expand(&mut self, lines: Vec<SourceLine>, depth: usize) -> Vec<SourceLine> {
+        let mut out: Vec<SourceLine> = vec![];
+        let mut conditions: Vec<Condition> = vec![];
+        let mut lines = lines.into_iter();
+        while let Some(source_line) = lines.next() {
+            let line = tokenize_line(&source_line.text);
+            let first = line.parts.first().map(|x| x.to_uppercase()).unwrap_or_default();
+            let active = conditions.last().is_none_or(|x| x.active);
+            if first == "%IF" || first == "%IFDEF" || first == "%IFNDEF" {
+                // conditions inside a skipped block are never checked
+                let value = active && match first.as_str() {
+                    "%IF" => self.evaluate(&line.parts[1..].join(" "), &source_line) != 0,
+                    _ => {
+                        let name = match line.parts.get(1) {
+                            Some(name) => name,
+                            None => panic!("Expected a name after {first} in {}", source_line.location()),
+                        };
+                        self.defines.iter().any(|x| &x.0 == name) == (first == "%IFDEF")
+                    }
+                };
+                conditions.push(Condition { active: value, parent_active: active, has_else: false, source_line: source_line.clone() });
+                continue;
+            }
+            else if first == "%ELSE" {
+                let condition = match conditions.last_mut() {
+                    Some(condition) => condition,
+                    None => panic!("%ELSE without %IF in {}", source_line.location()),
+                };
+                if condition.has_else {
+                    panic!("%IF has more than one %ELSE in {}", source_line.location());
                 }
-                "NOT" => {
-                    stream += "0110";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += "00";
+                condition.has_else = true;
+                condition.active = condition.parent_active && !condition.active;
+                continue;
+            }
+            else if first == "%ENDIF" {
+                if conditions.pop().is_none() {
+                    panic!("%ENDIF without %IF in {}", source_line.location());
                 }
-                "XOR" => {
-                    stream += "0111";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    stream += get_register(parts.next().unwrap());
+                continue;
+            }
+            else if !active {
+                continue;
+            }
+
+            if first == "%ASSIGN" {
+                // constants with a plain value can be checked by %IF, the first value given to a name is the one used
+                if let (Some(name), Some(value)) = (line.parts.get(1), line.parts.get(2).and_then(|x| parse_value(x))) {
+                    if !self.defines.iter().any(|x| &x.0 == name) {
+                        self.defines.push((name.clone(), value));
+                    }
                 }
-                "RPRT" => {
-                    stream += "100";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    let address = get_binary(parts.next().unwrap(), &vars);
-                    let bit3 = match address.as_str() {
-                        "00000000" => "000",
-                        "00000001" => "001",
-                        "00000010" => "010",
-                        "00000011" => "011",
-                        "00000100" => "100",
-                        "00000101" => "101",
-                        "00000110" => "110",
-                        "00000111" => "111",
-                        _ => panic!("Invalid port address {address}. Must be 0-7"),
+                out.push(source_line);
+            }
+            else if first == "%MACRO" {
+                let name = match line.parts.get(1) {
+                    Some(name) => name.to_uppercase(),
+                    None => panic!("Expected macro name in {}", source_line.location()),
+                };
+                let params = line.parts[2..].to_vec();
+                let mut body: Vec<String> = vec![];
+                let mut nested = 0;
+                loop {
+                    let inner = match lines.next() {
+                        Some(inner) => inner,
+                        None => panic!("Macro '{name}' is missing %ENDMACRO in {}", source_line.location()),
                     };
-                    stream += bit3;
+                    let inner_first = tokenize_line(&inner.text).parts.first().map(|x| x.to_uppercase()).unwrap_or_default();
+                    if inner_first == "%MACRO" {
+                        nested += 1;
+                    }
+                    else if inner_first == "%ENDMACRO" {
+                        if nested == 0 {
+                            break;
+                        }
+                        nested -= 1;
+                    }
+                    body.push(inner.text);
                 }
-                "WPRT" => {
-                    stream += "101";
-                    parts.next();
-                    stream += get_register(parts.next().unwrap());
-                    let address = get_binary(parts.next().unwrap(), &vars);
-                    let bit3 = match address.as_str() {
-                        "00000000" => "000",
-                        "00000001" => "001",
-                        "00000010" => "010",
-                        "00000011" => "011",
-                        "00000100" => "100",
-                        "00000101" => "101",
-                        "00000110" => "110",
-                        "00000111" => "111",
-                        _ => panic!("Invalid port address {address}. Must be 0-7"),
-                    };
-                    stream += bit3;
+                self.macros.retain(|x| x.name != name);
+                self.macros.push(Macro { name, params, body });
+            }
+            else if first == "%ENDMACRO" {
+                panic!("%ENDMACRO without %MACRO in {}", source_line.location());
+            }
+            else if first == "%INCLUDE" {
+                out.extend(self.include(&line, &source_line));
+            }
+            else if let Some(m) = self.macros.iter().find(|x| x.name == first).cloned() {
+                if depth >= MACRO_RECURSION_LIMIT {
+                    panic!("Macro '{}' goes past the recursion limit of {} in {}", m.name, MACRO_RECURSION_LIMIT, source_line.location());
                 }
-                ";" => {
-                    break;
+                let args = &line.parts[1..];
+                if args.len() != m.params.len() {
+                    panic!("Macro '{}' expects {} arguments but got {} in {}", m.name, m.params.len(), args.len(), source_line.location());
                 }
-                _ => {
-                    panic!("Invalid function call {part}");
+                if let Some(label) = &line.label {
+                    out.push(SourceLine { text: format!("{label}:"), ..source_line.clone() });
                 }
+
+                // every expansion gets its own id so local labels don't collide
+                self.expansions += 1;
+                let body = m.body.iter()
+                    .map(|x| SourceLine { text: substitute_macro_line(x, &m.params, args, self.expansions), ..source_line.clone() })
+                    .collect::<Vec<SourceLine>>();
+                out.extend(self.expand(body, depth + 1));
+            }
+            else {
+                out.push(source_line);
             }
-            out += &stream;
         }
+        if let Some(condition) = conditions.last() {
+            panic!("%IF is missing %ENDIF in {}", condition.source_line.location());
+        }
+        out
+    }
+    /// Evaluates the expression of an `%IF`, where 0 is false and anything else is true
+    fn evaluate(&self, expression: &str, source_line: &SourceLine) -> i32 {
+        let tokens = tokenize_expression(expression, source_line);
+        let mut parser = ExpressionParser { tokens, index: 0, defines: &self.defines, source_line };
+        let value = parser.or();
+        if parser.index != parser.tokens.len() {
+            panic!("Unexpected '{}' in %IF expression in {}", parser.tokens[parser.index], source_line.location());
+        }
+        value
     }
-    out
+    /// Reads the file named by an `%INCLUDE` line and preprocesses it
+    fn include(&mut self, line: &AsmLine, source_line: &SourceLine) -> Vec<SourceLine> {
+        let name = match line.parts.get(1) {
+            Some(name) if name.len() > 1 && name.starts_with('"') && name.ends_with('"') => &name[1..name.len() - 1],
+            _ => panic!("Expected %INCLUDE \"path\" in {}", source_line.location()),
+        };
+        let path = match Path::new(&source_line.file).parent() {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        };
+        let canonical = canonical_path(&path);
+        if self.includes.contains(&canonical) {
+            panic!("File '{}' includes itself in {}", path.display(), source_line.location());
+        }
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => panic!("Could not read file '{}': {e} in {}", path.display(), source_line.location()),
+        };
+
+        self.includes.push(canonical);
+        let lines = self.run(&contents, &path.to_string_lossy());
+        self.includes.pop();
+
+        // a label on the include line points at the start of the included code
+        let mut out: Vec<SourceLine> = vec![];
+        if let Some(label) = &line.label {
+            out.push(SourceLine { text: format!("{label}:"), ..source_line.clone() });
+        }
+        out.extend(lines);
+        out
+    }
+}
+
+/// Parses a number written as decimal, hexadecimal `0x3A`, binary `#00110100` or a character `'a'`
+pub(crate) fn parse_value(value: &str) -> Option<i32> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()
+    }
+    else if let Some(binary) = value.strip_prefix('#') {
+        i32::from_str_radix(binary, 2).ok()
+    }
+    else if value.len() == 3 && value.starts_with('\'') && value.ends_with('\'') {
+        value.chars().nth(1).map(|x| x as i32)
+    }
+    else {
+        value.parse::<i32>().ok()
+    }
+}
+
+/// Splits an `%IF` expression into values, names and operators
+fn tokenize_expression(expression: &str, source_line: &SourceLine) -> Vec<String> {
+    let chars = expression.chars().collect::<Vec<char>>();
+    let mut tokens: Vec<String> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        else if c == '\'' {
+            i = (i + 3).min(chars.len());
+        }
+        else if c.is_alphanumeric() || c == '_' || c == '#' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '#') {
+                i += 1;
+            }
+        }
+        else {
+            let pair = chars[i..(i + 2).min(chars.len())].iter().collect::<String>();
+            i += match pair.as_str() {
+                "==" | "!=" | "<=" | ">=" | "&&" | "||" => 2,
+                _ if "()!<>+-".contains(c) => 1,
+                _ => panic!("Unexpected '{c}' in %IF expression in {}", source_line.location()),
+            };
+        }
+        tokens.push(chars[start..i].iter().collect());
+    }
+    tokens
+}
+
+/// Evaluates `%IF` expressions with `||`, `&&`, comparisons, `+`, `-`, `!` and parentheses
+struct ExpressionParser<'a> {
+    tokens: Vec<String>,
+    index: usize,
+    defines: &'a [(String, i32)],
+    source_line: &'a SourceLine,
+}
+
+impl ExpressionParser<'_> {
+    fn accept(&mut self, ops: &[&str]) -> Option<String> {
+        match self.tokens.get(self.index) {
+            Some(token) if ops.contains(&token.as_str()) => {
+                self.index += 1;
+                Some(token.clone())
+            }
+            _ => None,
+        }
+    }
+    fn or(&mut self) -> i32 {
+        let mut value = self.and();
+        while self.accept(&["||"]).is_some() {
+            let right = self.and();
+            value = (value != 0 || right != 0) as i32;
+        }
+        value
+    }
+    fn and(&mut self) -> i32 {
+        let mut value = self.compare();
+        while self.accept(&["&&"]).is_some() {
+            let right = self.compare();
+            value = (value != 0 && right != 0) as i32;
+        }
+        value
+    }
+    fn compare(&mut self) -> i32 {
+        let mut value = self.sum();
+        while let Some(op) = self.accept(&["==", "!=", "<", ">", "<=", ">="]) {
+            let right = self.sum();
+            value = match op.as_str() {
+                "==" => value == right,
+                "!=" => value != right,
+                "<" => value < right,
+                ">" => value > right,
+                "<=" => value <= right,
+                _ => value >= right,
+            } as i32;
+        }
+        value
+    }
+    fn sum(&mut self) -> i32 {
+        let mut value = self.unary();
+        while let Some(op) = self.accept(&["+", "-"]) {
+            let right = self.unary();
+            value = if op == "+" { value + right } else { value - right };
+        }
+        value
+    }
+    fn unary(&mut self) -> i32 {
+        if self.accept(&["!"]).is_some() {
+            return (self.unary() == 0) as i32;
+        }
+        if self.accept(&["-"]).is_some() {
+            return -self.unary();
+        }
+        if self.accept(&["("]).is_some() {
+            let value = self.or();
+            if self.accept(&[")"]).is_none() {
+                panic!("Expected ')' in %IF expression in {}", self.source_line.location());
+            }
+            return value;
+        }
+        let token = match self.tokens.get(self.index) {
+            Some(token) => token.clone(),
+            None => panic!("Expected a value in %IF expression in {}", self.source_line.location()),
+        };
+        self.index += 1;
+        if let Some(value) = parse_value(&token) {
+            return value;
+        }
+        match self.defines.iter().find(|x| x.0 == token) {
+            Some(define) => define.1,
+            None => panic!("'{token}' is not defined in {}", self.source_line.location()),
+        }
+    }
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Replaces the parameters of a macro with the arguments and `%%label` with a label unique to the expansion
+fn substitute_macro_line(text: &str, params: &[String], args: &[String], expansion: usize) -> String {
+    let line = tokenize_line(text);
+    let replace = |word: &str| -> String {
+        if let Some(local) = word.strip_prefix("%%") {
+            return format!("__{local}_{expansion}");
+        }
+        match params.iter().position(|x| x == word) {
+            Some(i) => args[i].clone(),
+            None => word.to_string(),
+        }
+    };
+    let mut out = String::new();
+    if let Some(label) = &line.label {
+        out += &format!("{}: ", replace(label));
+    }
+    out += &line.parts.iter().map(|x| replace(x)).collect::<Vec<String>>().join(" ");
+    if let Some(comment) = &line.comment {
+        out += &format!(" {comment}");
+    }
+    out.trim().to_string()
+}
+
+/// Finds the address of every label in the program, along with the names brought in with `%EXTERN`
+fn get_labels(lines: &[SourceLine]) -> Vec<Symbol> {
+    let mut labels: Vec<Symbol> = vec![];
+    let mut constants: Vec<(String, i32)> = vec![];
+    let mut address = 0;
+    let mut has_code = false;
+    for source_line in lines {
+        let line = tokenize_line(&source_line.text);
+        let first = line.parts.first().map(|x| x.to_uppercase()).unwrap_or_default();
+        if first == "%ASSIGN" && line.parts.len() == 3 {
+            if let Some(value) = parse_value(&line.parts[2]) {
+                constants.push((line.parts[1].clone(), value));
+            }
+        }
+        else if first == "%EXTERN" {
+            for name in &line.parts[1..] {
+                labels.push(Symbol { name: name.clone(), value: Byte::zero(), kind: SymbolKind::Extern, global: false, source: source_line.clone() });
+            }
+        }
+        else if first == "%ORG" || first == ".ORG" {
+            let origin = match line.parts.get(1).and_then(|x| parse_value(x).or_else(|| constants.iter().find(|c| &c.0 == x).map(|c| c.1))) {
+                Some(origin) => origin,
+                None => panic!("%ORG needs a number or a constant assigned before it in {}", source_line.location()),
+            };
+            if has_code && origin < address {
+                panic!("%ORG {origin} is before the current address {address} in {}", source_line.location());
+            }
+            address = origin;
+        }
+        if let Some(label) = line.label {
+            if labels.iter().any(|x| x.name == label) {
+                panic!("Label '{label}' is defined more than once in {}", source_line.location());
+            }
+            if address > crate::vc_8bit::MAXBYTE {
+                panic!("Label '{label}' is past the end of memory in {}", source_line.location());
+            }
+            labels.push(Symbol { name: label, value: Byte::try_from(address).unwrap(), kind: SymbolKind::Label, global: false, source: source_line.clone() });
+        }
+        let parts = line.parts.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+        let size = match expand_pseudo_instruction(&parts, SCRATCH_ADDRESS) {
+            Some(instructions) => instructions.iter().map(|x| instruction_size(x.split(' ').next().unwrap_or(""))).sum::<i32>(),
+            None if first == "%BYTE" => data_values(&parts[1..], source_line).len() as i32,
+            None => parts.first().map_or(0, |x| instruction_size(x)),
+        };
+        has_code = has_code || size > 0;
+        address += size;
+    }
+    labels
+}
+
+/// # instruction_size
+/// Gets the number of bytes an instruction takes in memory
+/// # Arguments
+/// * `mnemonic` - The instruction name
+/// # Returns
+/// * `i32` - The number of bytes, 0 for directives
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::instruction_size;
+/// assert_eq!(instruction_size("MOV"), 2);
+/// assert_eq!(instruction_size("add"), 1);
+/// ```
+pub fn instruction_size(mnemonic: &str) -> i32 {
+    isa::lookup(&mnemonic.to_uppercase()).map_or(0, |x| x.size as i32)
 }
 /// # Get Register
 /// Gets the register from a string
@@ -302,12 +1254,12 @@ fn get_register(content: &str) -> &str {
 /// ```
 /// # Panics
 /// Will panic if value is not a number, hexadecimal, binary sequence, or variable
-pub fn get_binary(_content: &str, vars: &Vec<(String, Byte)>) -> String {
+pub fn get_binary(_content: &str, vars: &[(String, Byte)]) -> String {
     let content = _content.chars().take_while(|x| x != &';').collect::<String>();
     let binary_regex = Regex::new(r"^#[01]{8}$").unwrap(); // Matches #00000000 (binary)
     let hex_regex = Regex::new(r"^0x[0-9A-Fa-f]+$").unwrap(); // Matches 0x00 (hexadecimal)
-    let decimal_regex = Regex::new(r"\d+").unwrap(); // Matches any number (decimal)
-    let quote_regex = Regex::new(r"\'.\'").unwrap(); // Matches any quote (char)
+    let decimal_regex = Regex::new(r"^\d+$").unwrap(); // Matches any number (decimal)
+    let quote_regex = Regex::new(r"^\'.\'$").unwrap(); // Matches any quote (char)
 
     if binary_regex.is_match(content.as_str()) {
         // It's a binary string
@@ -318,7 +1270,7 @@ pub fn get_binary(_content: &str, vars: &Vec<(String, Byte)>) -> String {
     } else if quote_regex.is_match(content.as_str()) {
         // It's a quote string
         let c = content.chars().nth(1).unwrap();
-        (c as u8).to_string()
+        decimal_to_binary(c as u8)
     } else if decimal_regex.is_match(content.as_str()) {
         // It's a decimal string
         decimal_to_binary(content.parse().unwrap())
@@ -328,7 +1280,7 @@ pub fn get_binary(_content: &str, vars: &Vec<(String, Byte)>) -> String {
             vars.iter().find(|x| x.0 == content).unwrap().1.to_string()
         }
         else {
-            panic!("Variable not found");
+            panic!("Variable '{content}' not found");
         }
     }
 }
@@ -0,0 +1,96 @@
+//! # vcasm
+//! Assembles a file for the VC from the command line
+//!
+//! ```text
+//! vcasm [-D NAME[=value]]... [-c] <input.asm> [-o <output>]
+//! ```
+//! The binary is written as a string of '0' and '1' characters, next to the input with a `.bin` extension unless `-o` is given.
+//! A listing with the address and bytes of every line is written next to the binary with a `.lst` extension.
+//! With `-c` an object module is written with a `.o` extension instead, to be linked with `vclink`.
+//! The labels, constants and data regions are written as JSON with a `.sym.json` extension.
+//! When the output ends with `.vcimg` the program is packed in a `.vcimg` container along with its symbols.
+use vc_8bit::assembly::{self, AssemblyOptions};
+use vc_8bit::linker::ObjectModule;
+
+fn main() {
+    let mut options = AssemblyOptions::default();
+    let mut input: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut object = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-D" {
+            match args.next() {
+                Some(define) => options.add_define(&define),
+                None => usage("expected NAME=value after -D"),
+            }
+        }
+        else if let Some(define) = arg.strip_prefix("-D") {
+            options.add_define(define);
+        }
+        else if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(path),
+                None => usage("expected a path after -o"),
+            }
+        }
+        else if arg == "-c" {
+            object = true;
+        }
+        else if arg == "-h" || arg == "--help" {
+            usage("");
+        }
+        else if input.is_none() {
+            input = Some(arg);
+        }
+        else {
+            usage(&format!("unexpected argument '{arg}'"));
+        }
+    }
+
+    let input = match input {
+        Some(input) => input,
+        None => usage("no input file"),
+    };
+    let extension = if object { "o" } else { "bin" };
+    let output = output.unwrap_or_else(|| std::path::Path::new(&input).with_extension(extension).to_string_lossy().to_string());
+
+    let program = assembly::assemble_program_file(&input, &options);
+    write(&std::path::Path::new(&output).with_extension("lst").to_string_lossy(), &program.listing());
+    if object {
+        let name = std::path::Path::new(&input).file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+        let module = ObjectModule::from_program(&name, &program);
+        write(&output, &module.to_text());
+        println!("{} bytes written to {output}", module.code.len());
+        return;
+    }
+    write(&std::path::Path::new(&output).with_extension("sym.json").to_string_lossy(), &program.symbols_json());
+    if output.ends_with(".vcimg") {
+        let image = program.to_vcimg(true).to_bytes();
+        if let Err(e) = std::fs::write(&output, &image) {
+            eprintln!("vcasm: could not write '{output}': {e}");
+            std::process::exit(1);
+        }
+        println!("{} byte image written to {output}", image.len());
+        return;
+    }
+    let binary = program.binary();
+    write(&output, &binary);
+    println!("{} bytes written to {output}", binary.len() / 8);
+}
+
+fn write(path: &str, contents: &str) {
+    if let Err(e) = std::fs::write(path, contents) {
+        eprintln!("vcasm: could not write '{path}': {e}");
+        std::process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("vcasm: {error}");
+    }
+    eprintln!("usage: vcasm [-D NAME[=value]]... [-c] <input.asm> [-o <output>]");
+    std::process::exit(if error.is_empty() { 0 } else { 2 });
+}
@@ -0,0 +1,85 @@
+//! # vccfg
+//! Writes the control-flow graph of a program in the Graphviz DOT language
+//!
+//! ```text
+//! vccfg [-D NAME[=value]]... [-s] <file> [-o <output>]
+//! ```
+//! Files ending in `.asm` are assembled first, anything else is read as a program image in any format the loader knows.
+//! `-s` adds the line of source next to every instruction of an `.asm` file.
+//! The graph is printed unless `-o` is given, and can be drawn with `dot -Tsvg`.
+use vc_8bit::assembly::AssemblyOptions;
+use vc_8bit::cfg::{ControlFlowGraph, DotOptions};
+use vc_8bit::loader::ProgramImage;
+use vc_8bit::vc_8bit::Byte;
+
+fn main() {
+    let mut input: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut options = AssemblyOptions::default();
+    let mut dot_options = DotOptions::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-D" {
+            match args.next() {
+                Some(define) => options.add_define(&define),
+                None => usage("expected NAME=value after -D"),
+            }
+        }
+        else if let Some(define) = arg.strip_prefix("-D") {
+            options.add_define(define);
+        }
+        else if arg == "-o" {
+            match args.next() {
+                Some(path) => output = Some(path),
+                None => usage("expected a path after -o"),
+            }
+        }
+        else if arg == "-s" {
+            dot_options.source_lines = true;
+        }
+        else if arg == "-h" || arg == "--help" {
+            usage("");
+        }
+        else if input.is_none() {
+            input = Some(arg);
+        }
+        else {
+            usage("only one input file can be given");
+        }
+    }
+    let input = input.unwrap_or_else(|| usage("no input file"));
+
+    let graph = if input.ends_with(".asm") {
+        ControlFlowGraph::from_file(&input, &options)
+    }
+    else {
+        // the image is put at its load address so the addresses match the ones it runs at
+        let image = ProgramImage::from_file(&input);
+        let mut bytes = vec![Byte::zero(); image.load_address as usize];
+        bytes.extend(image.bytes);
+        ControlFlowGraph::new(&bytes, image.entry)
+    };
+    if let Some(name) = std::path::Path::new(&input).file_stem() {
+        dot_options.name = name.to_string_lossy().to_string();
+    }
+
+    let dot = graph.to_dot(&dot_options);
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, dot) {
+                eprintln!("vccfg: could not write '{path}': {e}");
+                std::process::exit(1);
+            }
+        }
+        None => print!("{dot}"),
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("vccfg: {error}");
+    }
+    eprintln!("usage: vccfg [-D NAME[=value]]... [-s] <file> [-o <output>]");
+    std::process::exit(if error.is_empty() { 0 } else { 2 });
+}
@@ -0,0 +1,73 @@
+//! # vcfmt
+//! Formats assembly files from the command line
+//!
+//! ```text
+//! vcfmt [--lower | --keep-case] [--base bin|hex|dec] [-w] <input.asm>...
+//! ```
+//! The formatted code is printed, or written back to the files with `-w`.
+use vc_8bit::formatter::{self, FormatOptions, MnemonicCase, NumberBase};
+
+fn main() {
+    let mut options = FormatOptions::default();
+    let mut inputs: Vec<String> = vec![];
+    let mut in_place = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--lower" {
+            options.mnemonic_case = MnemonicCase::Lower;
+        }
+        else if arg == "--keep-case" {
+            options.mnemonic_case = MnemonicCase::Keep;
+        }
+        else if arg == "--base" {
+            options.number_base = match args.next().as_deref() {
+                Some("bin") => Some(NumberBase::Binary),
+                Some("hex") => Some(NumberBase::Hex),
+                Some("dec") => Some(NumberBase::Decimal),
+                _ => usage("expected bin, hex or dec after --base"),
+            };
+        }
+        else if arg == "-w" {
+            in_place = true;
+        }
+        else if arg == "-h" || arg == "--help" {
+            usage("");
+        }
+        else {
+            inputs.push(arg);
+        }
+    }
+    if inputs.is_empty() {
+        usage("no input files");
+    }
+
+    for input in inputs {
+        let contents = match std::fs::read_to_string(&input) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("vcfmt: could not read '{input}': {e}");
+                std::process::exit(1);
+            }
+        };
+        let formatted = formatter::format_assembly(&contents, &options);
+        if !in_place {
+            print!("{formatted}");
+        }
+        else if formatted != contents {
+            if let Err(e) = std::fs::write(&input, formatted) {
+                eprintln!("vcfmt: could not write '{input}': {e}");
+                std::process::exit(1);
+            }
+            println!("formatted {input}");
+        }
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("vcfmt: {error}");
+    }
+    eprintln!("usage: vcfmt [--lower | --keep-case] [--base bin|hex|dec] [-w] <input.asm>...");
+    std::process::exit(if error.is_empty() { 0 } else { 2 });
+}
@@ -0,0 +1,61 @@
+//! # vclink
+//! Links object modules written by `vcasm -c` into a single program
+//!
+//! ```text
+//! vclink <module.o>... [-o <output>]
+//! ```
+//! The program is written as a string of '0' and '1' characters, to `a.bin` unless `-o` is given.
+//! A memory map with the address of every module and symbol is written next to it with a `.map` extension.
+use vc_8bit::linker::{self, ObjectModule};
+
+fn main() {
+    let mut inputs: Vec<String> = vec![];
+    let mut output = String::from("a.bin");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            match args.next() {
+                Some(path) => output = path,
+                None => usage("expected a path after -o"),
+            }
+        }
+        else if arg == "-h" || arg == "--help" {
+            usage("");
+        }
+        else {
+            inputs.push(arg);
+        }
+    }
+    if inputs.is_empty() {
+        usage("no input files");
+    }
+
+    let modules = inputs.iter().map(|path| match std::fs::read_to_string(path) {
+        Ok(contents) => ObjectModule::from_text(&contents),
+        Err(e) => {
+            eprintln!("vclink: could not read '{path}': {e}");
+            std::process::exit(1);
+        }
+    }).collect::<Vec<ObjectModule>>();
+
+    let program = linker::link(&modules);
+    write(&output, &program.binary());
+    write(&std::path::Path::new(&output).with_extension("map").to_string_lossy(), &program.memory_map());
+    println!("{} bytes written to {output}", program.bytes.len());
+}
+
+fn write(path: &str, contents: &str) {
+    if let Err(e) = std::fs::write(path, contents) {
+        eprintln!("vclink: could not write '{path}': {e}");
+        std::process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("vclink: {error}");
+    }
+    eprintln!("usage: vclink <module.o>... [-o <output>]");
+    std::process::exit(if error.is_empty() { 0 } else { 2 });
+}
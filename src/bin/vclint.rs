@@ -0,0 +1,74 @@
+//! # vclint
+//! Looks for problems in a program without running it
+//!
+//! ```text
+//! vclint [-D NAME[=value]]... <file>...
+//! ```
+//! Files ending in `.asm` are assembled first so problems can be shown with their line.
+//! Anything else is read as a program image in any format the loader knows.
+//! Exits with 1 if a problem was found.
+use vc_8bit::analyzer::{self, Diagnostic};
+use vc_8bit::assembly::AssemblyOptions;
+use vc_8bit::loader::ProgramImage;
+use vc_8bit::vc_8bit::Byte;
+
+fn main() {
+    let mut inputs: Vec<String> = vec![];
+    let mut options = AssemblyOptions::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-D" {
+            match args.next() {
+                Some(define) => options.add_define(&define),
+                None => usage("expected NAME=value after -D"),
+            }
+        }
+        else if let Some(define) = arg.strip_prefix("-D") {
+            options.add_define(define);
+        }
+        else if arg == "-h" || arg == "--help" {
+            usage("");
+        }
+        else {
+            inputs.push(arg);
+        }
+    }
+    if inputs.is_empty() {
+        usage("no input files");
+    }
+
+    let mut found = 0;
+    for path in &inputs {
+        let diagnostics: Vec<Diagnostic> = if path.ends_with(".asm") {
+            analyzer::analyze_file(path, &options)
+        }
+        else {
+            // the image is put at its load address so the addresses match the ones it runs at
+            let image = ProgramImage::from_file(path);
+            let mut bytes = vec![Byte::zero(); image.load_address as usize];
+            bytes.extend(image.bytes);
+            analyzer::analyze_bytes(&bytes, image.entry)
+        };
+        for diagnostic in &diagnostics {
+            // problems found in the source already name the file
+            match diagnostic.source {
+                Some(_) => println!("{diagnostic}"),
+                None => println!("{path}: {diagnostic}"),
+            }
+        }
+        found += diagnostics.len();
+    }
+    if found > 0 {
+        eprintln!("vclint: {found} problem{} found", if found == 1 { "" } else { "s" });
+        std::process::exit(1);
+    }
+}
+
+fn usage(error: &str) -> ! {
+    if !error.is_empty() {
+        eprintln!("vclint: {error}");
+    }
+    eprintln!("usage: vclint [-D NAME[=value]]... <file>...");
+    std::process::exit(if error.is_empty() { 0 } else { 2 });
+}
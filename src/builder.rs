@@ -0,0 +1,251 @@
+use crate::isa::Flag;
+use crate::vc_8bit::{Byte, Instruction, Reg};
+use crate::vcimg::ImageSymbol;
+
+/// A value, address or shift given to the builder, either a number or a label that is resolved when the program is built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(u8),
+    Label(String),
+}
+impl From<u8> for Value {
+    fn from(value: u8) -> Self {
+        Value::Number(value)
+    }
+}
+impl From<&str> for Value {
+    fn from(label: &str) -> Self {
+        Value::Label(label.to_string())
+    }
+}
+impl From<String> for Value {
+    fn from(label: String) -> Self {
+        Value::Label(label)
+    }
+}
+
+/// Something added to the program, in order
+#[derive(Debug, Clone)]
+enum Item {
+    /// An instruction and the label its value comes from, if any
+    Instruction(Instruction, Option<String>),
+    Label(String),
+    Data(Vec<u8>),
+}
+
+/// # ProgramBuilder
+/// Builds a program in Rust without writing assembly. Every method adds to the end of the program and
+/// values can be labels that are added before or after they are used.
+/// The instructions are encoded the same way the assembler encodes them.
+/// # Examples
+/// ```
+/// use vc_8bit::builder::ProgramBuilder;
+/// use vc_8bit::vc_8bit::Reg::*;
+/// use vc_8bit::assembly::compile_assembly_to_binary;
+/// let program = ProgramBuilder::new()
+///     .mov(R0, 5).mov(R1, 1)
+///     .label("loop").sub(R0, R1).jmp_zro("end").jmp("loop")
+///     .label("end").halt()
+///     .build();
+/// assert_eq!(program.binary(), compile_assembly_to_binary("MOV R0 5\nMOV R1 1\nloop: SUB R0 R1\nJMP_ZRO end\nJMP loop\nend: HALT"));
+/// assert_eq!(program.symbol("end"), Some(9));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+    items: Vec<Item>,
+}
+impl ProgramBuilder {
+    pub fn new() -> ProgramBuilder {
+        ProgramBuilder::default()
+    }
+    /// Names the address of the next instruction or data
+    pub fn label(mut self, name: &str) -> ProgramBuilder {
+        self.items.push(Item::Label(name.to_string()));
+        self
+    }
+    /// Adds bytes of data, like `%BYTE` in assembly
+    pub fn data(mut self, bytes: &[u8]) -> ProgramBuilder {
+        self.items.push(Item::Data(bytes.to_vec()));
+        self
+    }
+    /// Adds an instruction that has no label in it
+    pub fn instruction(mut self, instruction: Instruction) -> ProgramBuilder {
+        self.items.push(Item::Instruction(instruction, None));
+        self
+    }
+    /// Adds an instruction whose value is set from the number or label once the program is built
+    fn with_value(mut self, instruction: Instruction, value: Value) -> ProgramBuilder {
+        let item = match value {
+            Value::Number(number) => Item::Instruction(set_value(instruction, number), None),
+            Value::Label(label) => Item::Instruction(instruction, Some(label)),
+        };
+        self.items.push(item);
+        self
+    }
+
+    pub fn add(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Add { dst, src })
+    }
+    pub fn sub(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Sub { dst, src })
+    }
+    pub fn mul(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Mul { dst, src })
+    }
+    pub fn div(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Div { dst, src })
+    }
+    pub fn and(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::And { dst, src })
+    }
+    pub fn or(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Or { dst, src })
+    }
+    pub fn not(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Not { reg })
+    }
+    pub fn xor(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Xor { dst, src })
+    }
+    pub fn rprt(self, reg: Reg, port: u8) -> ProgramBuilder {
+        self.instruction(Instruction::Rprt { reg, port })
+    }
+    pub fn wprt(self, reg: Reg, port: u8) -> ProgramBuilder {
+        self.instruction(Instruction::Wprt { reg, port })
+    }
+    pub fn str(self, src: Reg, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Str { src, addr: 0 }, addr.into())
+    }
+    pub fn ldr(self, dst: Reg, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Ldr { dst, addr: 0 }, addr.into())
+    }
+    pub fn mov(self, dst: Reg, imm: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Mov { dst, imm: 0 }, imm.into())
+    }
+    pub fn cpy(self, dst: Reg, src: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Cpy { dst, src })
+    }
+    pub fn shl(self, reg: Reg, amount: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Shl { reg, amount: 0 }, amount.into())
+    }
+    pub fn shr(self, reg: Reg, amount: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Shr { reg, amount: 0 }, amount.into())
+    }
+    pub fn out(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Out { reg })
+    }
+    pub fn msg(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Msg { reg })
+    }
+    pub fn inc(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Inc { reg })
+    }
+    pub fn dec(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Dec { reg })
+    }
+    pub fn jmp(self, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Jmp { cond: None, addr: 0 }, addr.into())
+    }
+    pub fn jmp_neg(self, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Jmp { cond: Some(Flag::Negative), addr: 0 }, addr.into())
+    }
+    pub fn jmp_zro(self, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Jmp { cond: Some(Flag::Zero), addr: 0 }, addr.into())
+    }
+    pub fn jmp_abv(self, addr: impl Into<Value>) -> ProgramBuilder {
+        self.with_value(Instruction::Jmp { cond: Some(Flag::Above), addr: 0 }, addr.into())
+    }
+    pub fn cmp_neg(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Cmp { flag: Flag::Negative, reg })
+    }
+    pub fn cmp_zro(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Cmp { flag: Flag::Zero, reg })
+    }
+    pub fn cmp_abv(self, reg: Reg) -> ProgramBuilder {
+        self.instruction(Instruction::Cmp { flag: Flag::Above, reg })
+    }
+    pub fn halt(self) -> ProgramBuilder {
+        self.instruction(Instruction::Halt)
+    }
+
+    /// # build
+    /// Puts the labels at their addresses and encodes the program
+    /// # Returns
+    /// * `BuiltProgram` - The bytes of the program, starting at address 0, and its labels
+    /// # Panics
+    /// Will panic if a label is used but never added, a label is added twice, the program doesn't fit in memory
+    /// or a label that is used is past the end of memory
+    /// ```should_panic
+    /// use vc_8bit::builder::ProgramBuilder;
+    /// // `end` is at address 256 after 254 bytes of data and the jump
+    /// ProgramBuilder::new().data(&[0; 254]).jmp("end").label("end").build();
+    /// ```
+    pub fn build(&self) -> BuiltProgram {
+        let mut symbols: Vec<ImageSymbol> = vec![];
+        let mut address = 0;
+        for item in &self.items {
+            match item {
+                Item::Instruction(instruction, _) => address += instruction.size(),
+                Item::Data(bytes) => address += bytes.len(),
+                Item::Label(name) => {
+                    if symbols.iter().any(|x| x.name == *name) {
+                        panic!("Label '{name}' is added twice");
+                    }
+                    symbols.push(ImageSymbol { name: name.clone(), value: address as i32, is_label: true });
+                }
+            }
+        }
+        if address > 256 {
+            panic!("Program of {address} bytes does not fit in memory");
+        }
+
+        let mut bytes: Vec<Byte> = vec![];
+        for item in &self.items {
+            match item {
+                Item::Instruction(instruction, None) => bytes.extend(instruction.encode()),
+                Item::Instruction(instruction, Some(label)) => {
+                    let value = match symbols.iter().find(|x| x.name == *label) {
+                        Some(symbol) if symbol.value < 256 => symbol.value as u8,
+                        Some(symbol) => panic!("Label '{label}' is at address {} which is outside of memory", symbol.value),
+                        None => panic!("Label '{label}' not found"),
+                    };
+                    bytes.extend(set_value(*instruction, value).encode());
+                }
+                Item::Data(data) => bytes.extend(data.iter().map(|x| Byte::from_u8(*x))),
+                Item::Label(_) => {}
+            }
+        }
+        BuiltProgram { bytes, symbols }
+    }
+}
+
+/// Sets the value, address or shift of an instruction
+fn set_value(mut instruction: Instruction, value: u8) -> Instruction {
+    match &mut instruction {
+        Instruction::Str { addr, .. } | Instruction::Ldr { addr, .. } | Instruction::Jmp { addr, .. } => *addr = value,
+        Instruction::Mov { imm, .. } => *imm = value,
+        Instruction::Shl { amount, .. } | Instruction::Shr { amount, .. } => *amount = value,
+        _ => {}
+    }
+    instruction
+}
+
+/// # BuiltProgram
+/// A program made by `ProgramBuilder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuiltProgram {
+    /// The program, starting at address 0
+    pub bytes: Vec<Byte>,
+    /// The labels and their addresses, in the order they were added
+    pub symbols: Vec<ImageSymbol>,
+}
+impl BuiltProgram {
+    /// returns the program as a string of '0' and '1' characters, like `compile_assembly_to_binary`
+    pub fn binary(&self) -> String {
+        self.bytes.iter().map(|x| x.to_string()).collect()
+    }
+    /// returns the address of a label
+    pub fn symbol(&self, name: &str) -> Option<i32> {
+        self.symbols.iter().find(|x| x.name == name).map(|x| x.value)
+    }
+}
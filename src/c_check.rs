@@ -0,0 +1,507 @@
+use crate::c_lang::{Expr, Span, Stmt, Token, TokenType};
+
+/// # CheckError
+/// A problem found in the code by `check`, before any assembly is made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckError {
+    /// Where the problem starts in the code
+    pub span: Span,
+    pub message: String,
+}
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in line {} column {}", self.message, self.span.line, self.span.column)
+    }
+}
+
+/// The type of a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    Char,
+    UInt8,
+    /// `let` variables hold any type. Values that already have an error are also `Any` so they aren't reported twice
+    Any,
+    /// The value of a call to a function that doesn't return one
+    Void,
+}
+impl Type {
+    /// returns the type a type name like `uint8` or `char[]` is for
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name.trim_end_matches("[]") {
+            "bool" => Some(Type::Bool),
+            "char" => Some(Type::Char),
+            "uint8" => Some(Type::UInt8),
+            "let" => Some(Type::Any),
+            "void" => Some(Type::Void),
+            _ => None,
+        }
+    }
+    /// returns if a value of the type can be used where `expected` is needed. Nothing changes type without a cast
+    pub fn fits(self, expected: Type) -> bool {
+        self != Type::Void && expected != Type::Void && (self == expected || self == Type::Any || expected == Type::Any)
+    }
+}
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::UInt8 => "uint8",
+            Type::Any => "let",
+            Type::Void => "void",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The built-in functions with the types of their parameters and value
+const BUILT_IN: [(&str, &[Type], Type); 7] = [
+    ("print", &[Type::Char], Type::Void),
+    // the text is a string or a `char` array, see `text`
+    ("print_str", &[Type::Char], Type::Void),
+    ("out", &[Type::Any], Type::Void),
+    ("goto", &[Type::UInt8], Type::Void),
+    ("to_char", &[Type::UInt8], Type::Char),
+    ("write_port", &[Type::UInt8, Type::Any], Type::Void),
+    ("read_port", &[Type::UInt8], Type::UInt8),
+];
+
+/// # check
+/// Checks the meaning of a parsed program before it is compiled
+/// * variables are created before they are used and only used inside the block they are created in
+/// * a name is created once in each block, but can be created again in a block inside it
+/// * values are the type of the variable, parameter or return value they are given to. `let` variables take any type,
+///   and other values change type only with a cast like `(uint8)c`
+/// * operators are used with the types they work on, `&&` and `||` with `bool` and math with `uint8`, and `++` and `--` with a variable or array element
+/// * functions are defined before they are called, get the right number of arguments and don't call themselves
+/// * values that need to be known when compiling, like array sizes and port addresses, are constant, and constant array indexes are inside the array
+/// # Arguments
+/// * `program` - The statements from `parse`
+/// # Returns
+/// * `Vec<CheckError>` - Every problem in the order it is in the code, empty if there are none
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize};
+/// use vc_8bit::c_check::check;
+/// let errors = check(&parse(&tokenize("uint8 x = 'a';\nbool b = x && true;\nprint(y);")));
+/// let errors: Vec<String> = errors.iter().map(|x| x.to_string()).collect();
+/// assert_eq!(errors, [
+///     "Can't give char to uint8 variable 'x' in line 1 column 11",
+///     "Operator '&&' needs bool values but found uint8 in line 2 column 12",
+///     "Variable 'y' does not exist in line 3 column 7",
+/// ]);
+/// ```
+/// A cast changes the type, so characters can be used in math
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize};
+/// use vc_8bit::c_check::check;
+/// assert!(check(&parse(&tokenize("char c = 'h';\nuint8 n = (uint8)c - (uint8)'a';"))).is_empty());
+/// assert_eq!(check(&parse(&tokenize("char c = 'h';\nuint8 n = c - 'a';"))).len(), 1);
+/// ```
+/// A string creates a `char` array or is printed with `print_str`, and can't be empty
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize};
+/// use vc_8bit::c_check::check;
+/// assert!(check(&parse(&tokenize("char[] name = \"Ada\";\nprint_str(name);\nprint_str(\"Hi\");"))).is_empty());
+/// let errors = check(&parse(&tokenize("print(\"Hi\");\nchar[] empty = \"\";")));
+/// let errors: Vec<String> = errors.iter().map(|x| x.to_string()).collect();
+/// assert_eq!(errors, [
+///     "A string can only be used to create a char array or with 'print_str' in line 1 column 7",
+///     "Array 'empty' needs at least one element in line 2 column 16",
+/// ]);
+/// ```
+/// `++` and `--` store the new value back, so they need a variable or array element
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize};
+/// use vc_8bit::c_check::check;
+/// assert!(check(&parse(&tokenize("uint8 a = 5;\na++;\nuint8 list[2];\nlist[a - 5]--;"))).is_empty());
+/// let errors = check(&parse(&tokenize("uint8 a = 5++;")));
+/// let errors: Vec<String> = errors.iter().map(|x| x.to_string()).collect();
+/// assert_eq!(errors, ["Operator '++' needs a variable in line 1 column 11"]);
+/// ```
+pub fn check(program: &[Stmt]) -> Vec<CheckError> {
+    let mut checker = Checker { scopes: vec![vec![]], functions: vec![], function: None, errors: vec![] };
+    for stmt in program {
+        checker.stmt(stmt);
+    }
+    checker.errors
+}
+
+/// A variable the checker knows about
+struct Symbol {
+    name: String,
+    value_type: Type,
+    /// The number of elements, `None` if it isn't an array
+    length: Option<usize>,
+}
+/// A function defined in the code
+struct Signature {
+    name: String,
+    parameters: Vec<Type>,
+    return_type: Type,
+}
+
+struct Checker {
+    /// The variables of every block the checker is inside of, the innermost last
+    scopes: Vec<Vec<Symbol>>,
+    functions: Vec<Signature>,
+    /// The index of the function being checked, if any
+    function: Option<usize>,
+    errors: Vec<CheckError>,
+}
+impl Checker {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(CheckError { span, message });
+    }
+    fn find(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.iter().rev().flat_map(|x| x.iter()).find(|x| x.name == name)
+    }
+    fn declare(&mut self, name: &Token, value_type: Type, length: Option<usize>) {
+        if self.scopes.last().unwrap().iter().any(|x| x.name == name.value) {
+            self.error(name.span, format!("Variable '{}' is already created in this block", name.value));
+        }
+        self.scopes.last_mut().unwrap().push(Symbol { name: name.value.clone(), value_type, length });
+    }
+    /// returns the type a variable is created with, variables can't be `void`
+    fn variable_type(&mut self, type_name: &Token, name: &Token) -> Type {
+        match Type::from_name(&type_name.value) {
+            Some(Type::Void) | None => {
+                self.error(type_name.span, format!("Variable '{}' can't be {}", name.value, type_name.value));
+                Type::Any
+            }
+            Some(value_type) => value_type,
+        }
+    }
+    /// checks the statements in a block of their own
+    fn block(&mut self, body: &[Stmt]) {
+        self.scopes.push(vec![]);
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Decl { type_name, name, value } => {
+                let value_type = self.variable_type(type_name, name);
+                let length = match value {
+                    Expr::Array { values, .. } => {
+                        for value in values {
+                            self.expect(value, value_type, &format!("{value_type} array '{}'", name.value));
+                        }
+                        Some(values.len())
+                    }
+                    Expr::Literal(token) if token.token_type == TokenType::DoubleQuote && type_name.value.ends_with("[]") => {
+                        if !value_type.fits(Type::Char) {
+                            self.error(token.span, format!("Can't give string to {value_type} array '{}'", name.value));
+                        }
+                        if token.value.is_empty() {
+                            self.error(token.span, format!("Array '{}' needs at least one element", name.value));
+                        }
+                        Some(token.value.chars().count())
+                    }
+                    _ => {
+                        self.expect(value, value_type, &format!("{value_type} variable '{}'", name.value));
+                        None
+                    }
+                };
+                // the variable can't be used in its own value
+                self.declare(name, value_type, length);
+            }
+            Stmt::Array { type_name, name, size } => {
+                let value_type = self.variable_type(type_name, name);
+                if value_type == Type::Any {
+                    self.error(type_name.span, format!("Array '{}' needs a type", name.value));
+                }
+                self.expect(size, Type::UInt8, "an array size");
+                self.constant(size, "Array size");
+                let length = match size {
+                    Expr::Literal(token) => token.value.parse::<usize>().unwrap_or(0),
+                    _ => 1,
+                };
+                if length == 0 {
+                    self.error(size.token().span, format!("Array '{}' needs at least one element", name.value));
+                }
+                self.declare(name, value_type, Some(length));
+            }
+            Stmt::Assign { target, value } => {
+                let target_type = self.expr(target);
+                self.expect(value, target_type, &format!("{target_type} variable '{}'", target.token().value));
+            }
+            Stmt::If { condition, body, else_body, .. } => {
+                self.value(condition);
+                self.block(body);
+                self.block(else_body);
+            }
+            Stmt::While { condition, body, .. } => {
+                self.value(condition);
+                self.block(body);
+            }
+            Stmt::DoWhile { body, condition, .. } => {
+                self.block(body);
+                self.value(condition);
+            }
+            Stmt::For { init, condition, step, body, .. } => {
+                // a variable created in `init` is only in the loop
+                self.scopes.push(vec![]);
+                if let Some(init) = init {
+                    self.stmt(init);
+                }
+                if let Some(condition) = condition {
+                    self.value(condition);
+                }
+                if let Some(step) = step {
+                    self.stmt(step);
+                }
+                self.block(body);
+                self.scopes.pop();
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+            Stmt::Expr(expr) => {
+                self.expr(expr);
+            }
+            Stmt::Function { return_type, name, parameters, body } => {
+                if BUILT_IN.iter().any(|x| x.0 == name.value) || self.functions.iter().any(|x| x.name == name.value) {
+                    self.error(name.span, format!("Function '{}' is already defined", name.value));
+                }
+                let return_type = match Type::from_name(&return_type.value) {
+                    Some(Type::Any) | None => {
+                        self.error(return_type.span, format!("Function '{}' can't return {}", name.value, return_type.value));
+                        Type::Any
+                    }
+                    Some(value_type) => value_type,
+                };
+
+                // the parameters are in the same block as the body
+                self.scopes.push(vec![]);
+                let mut types: Vec<Type> = vec![];
+                for (type_name, parameter) in parameters {
+                    if type_name.value.ends_with("[]") {
+                        self.error(parameter.span, format!("Parameter '{}' can't be an array", parameter.value));
+                    }
+                    let value_type = self.variable_type(type_name, parameter);
+                    self.declare(parameter, value_type, None);
+                    types.push(value_type);
+                }
+                self.functions.push(Signature { name: name.value.clone(), parameters: types, return_type });
+                self.function = Some(self.functions.len() - 1);
+                for stmt in body {
+                    self.stmt(stmt);
+                }
+                self.function = None;
+                self.scopes.pop();
+            }
+            Stmt::Return { token, value } => {
+                let function = &self.functions[self.function.unwrap()];
+                let (name, return_type) = (function.name.clone(), function.return_type);
+                match value {
+                    Some(value) if return_type == Type::Void => self.error(value.token().span, format!("Function '{name}' does not return a value")),
+                    Some(value) => self.expect(value, return_type, &format!("the value of '{name}'")),
+                    None if return_type != Type::Void => self.error(token.span, format!("Function '{name}' needs to return {return_type}")),
+                    None => {}
+                }
+            }
+        }
+    }
+    /// checks that an expression has a value and returns its type. Conditions can be any type that has a value
+    fn value(&mut self, expr: &Expr) -> Type {
+        let value_type = self.expr(expr);
+        if value_type == Type::Void {
+            self.error(expr.token().span, format!("Function '{}' does not return a value", expr.token().value));
+            return Type::Any;
+        }
+        value_type
+    }
+    /// checks that an expression has the type expected
+    /// * `what` - What the value is given to, like `uint8 variable 'x'`
+    fn expect(&mut self, expr: &Expr, expected: Type, what: &str) {
+        let value_type = self.value(expr);
+        if !value_type.fits(expected) {
+            self.error(expr.token().span, format!("Can't give {value_type} to {what}"));
+        }
+    }
+    /// checks a value that is printed as text, which is a string or the name of a `char` array
+    fn text(&mut self, expr: &Expr, what: &str) {
+        match expr {
+            Expr::Literal(token) if token.token_type == TokenType::DoubleQuote => {}
+            Expr::Variable(token) if self.find(&token.value).is_some_and(|x| x.length.is_some()) => {
+                let value_type = self.find(&token.value).unwrap().value_type;
+                if !value_type.fits(Type::Char) {
+                    self.error(token.span, format!("Can't give {value_type} array to {what}"));
+                }
+            }
+            _ => {
+                let value_type = self.expr(expr);
+                if value_type != Type::Any {
+                    self.error(expr.token().span, format!("Can't give {value_type} to {what}, it needs a string or a char array"));
+                }
+            }
+        }
+    }
+    /// checks a value that needs to be known when compiling
+    fn constant(&mut self, expr: &Expr, what: &str) {
+        if !matches!(expr, Expr::Literal(token) if token.token_type == TokenType::Number) {
+            self.error(expr.token().span, format!("{what} requires constant value"));
+        }
+    }
+
+    /// checks an expression and returns its type
+    fn expr(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(token) => match token.token_type {
+                TokenType::Boolean => Type::Bool,
+                TokenType::SingleQuote => Type::Char,
+                TokenType::DoubleQuote => {
+                    self.error(token.span, "A string can only be used to create a char array or with 'print_str'".to_string());
+                    Type::Any
+                }
+                _ => {
+                    if token.value.parse::<u32>().is_ok_and(|x| x > 255) {
+                        self.error(token.span, format!("Number {} is more than 255", token.value));
+                    }
+                    Type::UInt8
+                }
+            }
+            Expr::Variable(token) => match self.find(&token.value) {
+                Some(Symbol { length: Some(_), .. }) => {
+                    self.error(token.span, format!("Array '{}' needs an index like '{}[0]'", token.value, token.value));
+                    Type::Any
+                }
+                Some(symbol) => symbol.value_type,
+                None => {
+                    self.error(token.span, format!("Variable '{}' does not exist", token.value));
+                    Type::Any
+                }
+            }
+            Expr::Array { token, .. } => {
+                self.error(token.span, "Array values can only be used to create an array".to_string());
+                Type::Any
+            }
+            Expr::Index { array, index } => {
+                self.expect(index, Type::UInt8, "an array index");
+                let (value_type, length) = match self.find(&array.value) {
+                    Some(Symbol { value_type, length: Some(length), .. }) => (*value_type, *length),
+                    Some(_) => {
+                        self.error(array.span, format!("Variable '{}' is not an array", array.value));
+                        return Type::Any;
+                    }
+                    None => {
+                        self.error(array.span, format!("Variable '{}' does not exist", array.value));
+                        return Type::Any;
+                    }
+                };
+                // indexes worked out while running are checked with `CompileOptions::bounds_checks`
+                if let Expr::Literal(token) = index.as_ref() {
+                    if token.value.parse::<usize>().is_ok_and(|x| x >= length) {
+                        self.error(token.span, format!("Index {} is outside of array '{}'", token.value, array.value));
+                    }
+                }
+                value_type
+            }
+            Expr::Unary { op, operand } => match op.token_type {
+                // `!` flips every bit, so it works on any type
+                TokenType::Not => self.value(operand),
+                // the new value is stored back, so there has to be somewhere to store it
+                TokenType::Increment | TokenType::Decrement => {
+                    if !matches!(operand.as_ref(), Expr::Variable(_) | Expr::Index { .. }) {
+                        self.error(operand.token().span, format!("Operator '{}' needs a variable", op.value));
+                    }
+                    self.operands(op, &[operand], Type::UInt8)
+                }
+                _ => self.operands(op, &[operand], Type::UInt8),
+            }
+            Expr::Binary { op, left, right } => self.binary(op, left, right),
+            // any value can be turned into uint8, char or bool
+            Expr::Cast { type_name, operand } => {
+                self.value(operand);
+                match Type::from_name(&type_name.value) {
+                    Some(value_type @ (Type::Bool | Type::Char | Type::UInt8)) if !type_name.value.ends_with("[]") => value_type,
+                    _ => {
+                        self.error(type_name.span, format!("Can't cast to {}", type_name.value));
+                        Type::Any
+                    }
+                }
+            }
+            Expr::Call { name, arguments } => self.call(name, arguments),
+        }
+    }
+    /// checks that the values of an operator are the type it works on, and returns the type of its value
+    fn operands(&mut self, op: &Token, values: &[&Expr], expected: Type) -> Type {
+        for value in values {
+            let value_type = self.value(value);
+            if !value_type.fits(expected) {
+                self.error(op.span, format!("Operator '{}' needs {expected} values but found {value_type}", op.value));
+                return Type::Any;
+            }
+        }
+        expected
+    }
+    fn binary(&mut self, op: &Token, left: &Expr, right: &Expr) -> Type {
+        match op.token_type {
+            TokenType::AndAnd | TokenType::OrOr => self.operands(op, &[left, right], Type::Bool),
+            TokenType::Plus | TokenType::Dash | TokenType::Star | TokenType::Slash => self.operands(op, &[left, right], Type::UInt8),
+            TokenType::ShiftLeft | TokenType::ShiftRight => {
+                let value_type = self.value(left);
+                self.expect(right, Type::UInt8, "a shift");
+                self.constant(right, "Shifting");
+                value_type
+            }
+            // `&`, `|` and `^` work on the bits of any type, comparisons on two values of the same type
+            _ => {
+                let left_type = self.value(left);
+                let right_type = self.value(right);
+                let is_compare = !matches!(op.token_type, TokenType::And | TokenType::Or | TokenType::XOR);
+                let is_order = matches!(op.token_type, TokenType::GreaterThan | TokenType::GreaterThanOrEqualTo | TokenType::LessThan | TokenType::LessThanOrEqualTo);
+                if !left_type.fits(right_type) {
+                    self.error(op.span, format!("Operator '{}' needs values of the same type but found {left_type} and {right_type}", op.value));
+                }
+                else if is_order && (left_type == Type::Bool || right_type == Type::Bool) {
+                    self.error(op.span, format!("Operator '{}' needs uint8 or char values but found bool", op.value));
+                }
+                match (is_compare, left_type) {
+                    (true, _) => Type::Bool,
+                    (false, Type::Any) => right_type,
+                    (false, _) => left_type,
+                }
+            }
+        }
+    }
+    fn call(&mut self, name: &Token, arguments: &[Expr]) -> Type {
+        let (parameters, return_type) = if let Some(built_in) = BUILT_IN.iter().find(|x| x.0 == name.value) {
+            // ports and jumps are in the instruction, so they are known when compiling
+            match name.value.as_str() {
+                "goto" => arguments.iter().take(1).for_each(|x| self.constant(x, "Jumping")),
+                "write_port" | "read_port" => arguments.iter().take(1).for_each(|x| self.constant(x, "Port address")),
+                _ => {}
+            }
+            (built_in.1.to_vec(), built_in.2)
+        }
+        else if let Some(index) = self.functions.iter().position(|x| x.name == name.value) {
+            if self.function == Some(index) {
+                self.error(name.span, format!("Function '{}' can't call itself, there is no stack for recursion", name.value));
+            }
+            (self.functions[index].parameters.clone(), self.functions[index].return_type)
+        }
+        else {
+            self.error(name.span, format!("Function '{}' not defined", name.value));
+            arguments.iter().for_each(|x| { self.expr(x); });
+            return Type::Any;
+        };
+
+        if arguments.len() != parameters.len() {
+            self.error(name.span, format!("Function '{}' takes {} argument(s) but {} were given", name.value, parameters.len(), arguments.len()));
+        }
+        for (i, (argument, parameter)) in arguments.iter().zip(parameters).enumerate() {
+            let what = format!("argument {} of '{}'", i + 1, name.value);
+            if name.value == "print_str" {
+                self.text(argument, &what);
+            }
+            else {
+                self.expect(argument, parameter, &what);
+            }
+        }
+        return_type
+    }
+}
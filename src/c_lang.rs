@@ -1,8 +1,8 @@
-use std::io::{self, Result};
 use std::vec::Vec;
-use regex::Regex;
 
 use crate::assembly::{self, compile_assembly_to_binary, string_to_bytes};
+use crate::c_check;
+use crate::isa;
 use crate::vc_8bit::{self, Byte};
 /// # Compile
 /// Compiles code to Assembly
@@ -19,986 +19,844 @@ use crate::vc_8bit::{self, Byte};
 /// # Panics
 /// This function will panic if the code is invalid
 pub fn compile(contents: &String) -> String {
-    let fixed_program = constants_and_bytes(contents);
-    let lex: Vec<Line> = get_lexer_lines(&fixed_program);
-    let par = parse(lex);
-    interpret(par.clone())
+    let program = parse(&tokenize(contents));
+    interpret(&program)
 }
 
-pub fn constants_and_bytes(contents: &String) -> String {
-    // Regular expression to match binary literals
-    let re = Regex::new(r"0b[01]+").unwrap();
-
-    let updated_program = re.replace_all(&contents, |caps: &regex::Captures| {
-        // Extract the binary number without the "0b" prefix
-        let binary_str = &caps[0][2..]; 
-        // Convert the binary string to an integer
-        let integer_value = isize::from_str_radix(binary_str, 2).unwrap();
-        // Return the integer value as a string
-        integer_value.to_string()
-    }).into_owned();
-
-    let mut constants: Vec<(String, String, bool)> = vec![];
-    let mut lines: Vec<String> = vec![];
-    for line in updated_program.lines().map(|x| x.to_string()).collect::<Vec<String>>() {
-        let lexer_line = get_lexer_line(line.as_str(), 0);
-        let parts = lexer_line.clone().tokens.iter().map(|x| x.value.to_string()).collect::<Vec<String>>();
-        if parts.len() == 4 && parts[0] == "const" && parts[2] == "=" {
-            let name = parts[1].to_string();
-            let value = parts[3].to_string();
-            let is_quote = lexer_line.tokens.last().unwrap().token_type == TokenType::SingleQuote;
-            constants.push((name.to_string(), value.to_string(), is_quote));
-        } 
-        else {
-            if parts.iter().any(|x| constants.iter().any(|y| x == &y.0)) {
-                let mut new_parts: Vec<String> = vec![]; 
-                for i in 0..parts.len() {
-                    match constants.iter().find(|x| x.0 == parts[i]) {
-                        Some(c) => {
-                            if c.2 {
-                                new_parts.push(format!("'{}'", c.1.to_string()))
-                            } else {
-                                new_parts.push(c.1.to_string())
-                            }
-                        }
-                        None => new_parts.push(parts[i].to_string()),
-                    };
-                }
-                lines.push(new_parts.join(" "));
-            }
-            else {
-                lines.push(line.to_string());
-            }
+/// # compile_with_variables
+/// Compiles code to Assembly and returns where each variable was placed, see `variables_json`
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang;
+/// let (asm, variables) = c_lang::compile_with_variables("uint8 x = 5;");
+/// assert_eq!(variables[0].name, "x");
+/// assert_eq!(variables[0].address.to_i32(), 254);
+/// ```
+pub fn compile_with_variables(contents: &str) -> (String, Vec<Variable>) {
+    interpret_with_variables(&parse(&tokenize(contents)))
+}
+/// # variables_json
+/// Writes where each variable was placed as JSON for debuggers and other tools
+/// ```text
+/// {
+///   "version": 1,
+///   "variables": [
+///     { "name": "x", "type": "uint8", "address": 254, "array": false },
+///     { "name": "list", "type": "char", "address": 253, "array": true, "elements": [
+///       { "index": 0, "address": 253 },
+///       { "index": 1, "address": 252 }
+///     ] }
+///   ]
+/// }
+/// ```
+/// `type` is `bool`, `char`, `uint8` or `let`. The address of an array is the address of its first element.
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang;
+/// let (_, variables) = c_lang::compile_with_variables("char[] list = {'a', 'b'};\nuint8 x = 5;");
+/// let json = c_lang::variables_json(&variables);
+/// assert!(json.contains(r#"{ "name": "x", "type": "uint8", "address": 252, "array": false }"#));
+/// assert!(json.contains(r#"{ "index": 1, "address": 253 }"#));
+/// ```
+pub fn variables_json(variables: &[Variable]) -> String {
+    let mut entries: Vec<String> = vec![];
+    let mut arrays: Vec<&str> = vec![];
+    for variable in variables {
+        let var_type = match variable.var_type {
+            VariableType::Bool => "bool",
+            VariableType::Char => "char",
+            VariableType::UInt8 => "uint8",
+            VariableType::None => "let",
+        };
+        if !variable.is_array {
+            entries.push(format!("    {{ \"name\": {}, \"type\": \"{var_type}\", \"address\": {}, \"array\": false }}", assembly::json_string(&variable.name), variable.address.to_i32()));
+            continue;
         }
-    };
-    lines.join("\n")
+        // array elements are stored as "name|index", they are put together under the name of the array
+        let name = variable.name.split('|').next().unwrap_or("");
+        if arrays.contains(&name) {
+            continue;
+        }
+        arrays.push(name);
+        let elements = variables.iter().filter(|x| x.is_array && x.name.split('|').next() == Some(name)).map(|x| {
+            format!("      {{ \"index\": {}, \"address\": {} }}", x.name.split('|').nth(1).unwrap_or("0"), x.address.to_i32())
+        }).collect::<Vec<String>>();
+        entries.push(format!("    {{ \"name\": {}, \"type\": \"{var_type}\", \"address\": {}, \"array\": true, \"elements\": [\n{}\n    ] }}", assembly::json_string(name), variable.address.to_i32(), elements.join(",\n")));
+    }
+    format!("{{\n  \"version\": 1,\n  \"variables\": {}\n}}\n", assembly::json_list(&entries))
 }
 
 #[derive(Clone, Debug, PartialEq)] 
 pub enum TokenType {
     Plus, Dash, Star, Slash, Equal, GreaterThan, LessThan, SingleQuote, Not, EqualCompare, And, Or, Identifier, 
     OpenParen, CloseParen, OpenCurley, CloseCurley, Comma, NotEqual, AndAnd, OrOr, OpenBracket, CloseBracket, XOR,
-    GreaterThanOrEqualTo, LessThanOrEqualTo, Number, TypeName, Statement, Boolean, ShiftLeft, ShiftRight, None, Increment, Decrement
+    GreaterThanOrEqualTo, LessThanOrEqualTo, Number, TypeName, Statement, Boolean, ShiftLeft, ShiftRight, None, Increment, Decrement,
+    Semicolon, DoubleQuote
 }
 
-#[derive(Clone, Debug)]
-pub struct Line {
-    pub tokens: Vec<Token>, pub number: i32
+/// Where a token starts in the code. `line` and `column` start at 1, `offset` is in bytes and starts at 0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub offset: usize, pub line: usize, pub column: usize
 }
 #[derive(Clone, Debug, PartialEq)] 
 pub struct Token {
-    pub token_type: TokenType, pub value: String
+    pub token_type: TokenType, pub value: String, pub span: Span
 }
-pub fn get_lexer_lines(contents: &str) -> Vec<Line> {
-    let lines: Vec<&str> = contents.split(";").filter(|&x| x.trim() != "").collect();
-    let mut lexer_lines: Vec<Line> = Vec::new();
-    
-    // return lexer lines
-    let mut line_number = 0;
-    for line in lines {
-        line_number += 1;
-        let l = get_lexer_line(line.trim(), line_number);
-        lexer_lines.push(l);
+impl Token {
+    /// returns a token that isn't from the code
+    pub fn new(token_type: TokenType, value: &str) -> Token {
+        Token { token_type, value: value.to_string(), span: Span::default() }
     }
-    lexer_lines
 }
-pub fn get_lexer_line(line: &str, line_number: i32, ) -> Line {
-    let chars: Vec<&str> = line.split("").collect();
-    let mut tokens: Vec<Token> = Vec::new();
-    let mut single: bool = false;
-    let mut skip: usize = 0;
-    let mut number: String = String::new();
-    let mut alphabetical: String = String::new();
 
-    // loop through chars
-    for (j, &c) in chars.iter().enumerate() {
-        if skip > 0 { 
-            skip -= 1;
-            continue;
-        }
-        // is dot
-        if c == "." {
-            // and is number
-            if number != "".to_string() {
-                number += c;
-            }
-            // and next is number
-            else if chars.len() > j + 1 && parse_str_to_i32(&chars[j + 1].to_string()).is_ok() {
-                number += c;
-            }
-        }
-        // is slash
-        else if c == "/" {
-            // and apart of comment
-            if chars.len() > j + 1 && chars[j + 1] == "/" {
-                break;
-            }
-            // and by itself 
-            else {
-                tokens.push(Token { token_type: TokenType::Slash, value: c.to_string() });
-                single = true;
-            }
-        }
-        // is less than
-        else if c == "<" {
-            // and apart of less than or equal to
-            if chars.len() > j + 1 && chars[j + 1] == "=" {
-                tokens.push(Token { token_type: TokenType::LessThanOrEqualTo, value: "<=".to_string() });
-                skip = 2;
-            }
-            else if chars.len() > j + 1 && chars[j + 1] == "<" {
-                tokens.push(Token { token_type: TokenType::ShiftLeft, value: "<<".to_string() });
-                skip = 2;
-            }
-            // and by itself 
-            else {
-                tokens.push(Token { token_type: TokenType::LessThan, value: c.to_string() });
-                single = true;
-            }
-        }
-        // is greater than
-        else if c == ">" {
-            if chars.len() > j + 1 && chars[j + 1] == "=" {
-                tokens.push(Token { token_type: TokenType::GreaterThanOrEqualTo, value: ">=".to_string() });
-                skip = 2;
-            }
-            else if chars.len() > j + 1 && chars[j + 1] == ">" {
-                tokens.push(Token { token_type: TokenType::ShiftRight, value: ">>".to_string() });
-                skip = 2;
-            }
-            // and by itself 
-            else {
-                tokens.push(Token { token_type: TokenType::GreaterThan, value: c.to_string() });
-                single = true;
+/// # tokenize
+/// Splits code into tokens and remembers where each one starts.
+/// Comments are skipped, numbers written as `0x` or `0b` become decimal and chars and strings become the characters they stand for
+/// # Arguments
+/// * `contents` - code
+/// # Returns
+/// * `Vec<Token>` - The tokens, including `;`
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{tokenize, TokenType};
+/// let tokens = tokenize("char c = ';'; // a; comment\nuint8 x = 0x1f;");
+/// assert_eq!(tokens[3].token_type, TokenType::SingleQuote);
+/// assert_eq!(tokens[3].value, ";");
+/// assert_eq!(tokens[8].value, "31");
+/// assert_eq!((tokens[8].span.line, tokens[8].span.column, tokens[8].span.offset), (2, 11, 38));
+/// let tokens = tokenize("print_str(\"Hi\\n\");");
+/// assert_eq!((&tokens[2].token_type, tokens[2].value.as_str()), (&TokenType::DoubleQuote, "Hi\n"));
+/// ```
+/// # Panics
+/// Will panic if a character can't start a token, a char, string or comment isn't closed or a number is invalid
+pub fn tokenize(contents: &str) -> Vec<Token> {
+    let chars: Vec<(usize, char)> = contents.char_indices().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut line_start = 0;
+
+    let peek = |i: usize| chars.get(i).map(|x| x.1).unwrap_or('\0');
+    // a character of a char or string, where `\` starts an escape. Returns the character and how many characters are used to write it
+    let character = |i: usize, line: usize| match (peek(i), peek(i + 1)) {
+        ('\\', 'x') => {
+            let hex: String = [peek(i + 2), peek(i + 3)].iter().collect();
+            match u8::from_str_radix(&hex, 16) {
+                Ok(value) => (value as char, 4),
+                Err(_) => panic!("Invalid escape character '\\x{}' in line {}", hex, line),
             }
         }
-        // is equal to 
-        else if c == "=" {
-            if chars.len() > j + 1 && chars[j + 1] == "=" {
-                tokens.push(Token { token_type: TokenType::EqualCompare, value: "==".to_string() });
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::Equal, value: c.to_string() });
-                single = true;
-            }
+        ('\\', escape) => {
+            let value = match escape {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\'' => '\'',
+                '"' => '"',
+                '\\' => '\\',
+                _ => panic!("Invalid escape character '\\{}' in line {}", escape, line),
+            };
+            (value, 2)
         }
-        // is not
-        else if c == "!" {
-            if chars.len() > j + 1 && chars[j + 1] == "=" {
-                tokens.push(Token { token_type: TokenType::NotEqual, value: "!=".to_string() });
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::Not, value: c.to_string() });
-                single = true;
-            }
+        (value, _) => (value, 1),
+    };
+    while i < chars.len() {
+        let (offset, c) = chars[i];
+        let span = Span { offset, line, column: i - line_start + 1 };
+
+        // whitespace and comments
+        if c == '\n' {
+            line += 1;
+            line_start = i + 1;
+            i += 1;
+            continue;
         }
-        else if c == "+" {
-            if chars.len() > j + 1 && chars[j + 1] == "+" {
-                tokens.push(Token { token_type: TokenType::Identifier, value: alphabetical.trim().to_string() });
-                tokens.push(Token { token_type: TokenType::Increment, value: "++".to_string() });
-                alphabetical = String::new();
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::Plus, value: "+".to_string() });
-                single = true;
-            }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
         }
-        else if c == "-" {
-            if chars.len() > j + 1 && chars[j + 1] == "-" {
-                tokens.push(Token { token_type: TokenType::Identifier, value: alphabetical.trim().to_string() });
-                tokens.push(Token { token_type: TokenType::Decrement, value: "--".to_string() });
-                alphabetical = String::new();
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::Dash, value: "-".to_string() });
-                single = true;
+        if c == '/' && peek(i + 1) == '/' {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
             }
+            continue;
         }
-        else if c == "&" {
-            if chars.len() > j + 1 && chars[j + 1] == "&" {
-                tokens.push(Token { token_type: TokenType::AndAnd, value: "&&".to_string() });
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::And, value: "&".to_string() });
-                single = true;
+        if c == '/' && peek(i + 1) == '*' {
+            i += 2;
+            loop {
+                if i >= chars.len() {
+                    panic!("Comment is never closed in line {}", span.line);
+                }
+                if chars[i].1 == '*' && peek(i + 1) == '/' {
+                    i += 2;
+                    break;
+                }
+                if chars[i].1 == '\n' {
+                    line += 1;
+                    line_start = i + 1;
+                }
+                i += 1;
             }
+            continue;
         }
-        // is or
-        else if c == "|" {
-            if chars.len() > j + 1 && chars[j + 1] == "|" {
-                tokens.push(Token { token_type: TokenType::OrOr, value: "||".to_string() });
-                skip = 2;
-            }
-            else {
-                tokens.push(Token { token_type: TokenType::Or, value: "|".to_string() });
-                single = true;
+
+        // words
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                i += 1;
             }
+            let mut word: String = chars[start..i].iter().map(|x| x.1).collect();
+            let token_type = match word.as_str() {
+                "uint8" | "char" | "bool" | "let" | "void" => {
+                    // array types are written with the brackets right after the type
+                    if !matches!(word.as_str(), "let" | "void") && peek(i) == '[' && peek(i + 1) == ']' {
+                        word += "[]";
+                        i += 2;
+                    }
+                    TokenType::TypeName
+                }
+                "if" | "else" | "while" | "for" | "do" | "break" | "continue" | "return" => TokenType::Statement,
+                "true" | "false" => TokenType::Boolean,
+                _ => TokenType::Identifier,
+            };
+            tokens.push(Token { token_type, value: word, span });
+            continue;
         }
-        // is array
-        else if c == "[" {
-            if chars.len() > j + 1 && chars[j + 1] == "]" && !alphabetical.is_empty() {
-                alphabetical += "[]";
-                skip = 1;
+
+        // numbers
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].1.is_ascii_alphanumeric() || chars[i].1 == '_') {
+                i += 1;
             }
-            else {
-                tokens.push(Token { token_type: TokenType::OpenBracket, value: c.to_string() });
-                single = true;
+            let text: String = chars[start..i].iter().map(|x| x.1).collect();
+            let digits = text.replace('_', "");
+            let value = match digits.get(..2) {
+                Some("0x") | Some("0X") => u32::from_str_radix(&digits[2..], 16),
+                Some("0b") | Some("0B") => u32::from_str_radix(&digits[2..], 2),
+                _ => digits.parse::<u32>(),
+            };
+            match value {
+                Ok(value) => tokens.push(Token { token_type: TokenType::Number, value: value.to_string(), span }),
+                Err(_) => panic!("Invalid number '{}' in line {}", text, span.line),
             }
+            continue;
         }
-        // is apart of single quote
-        else if c == "'" {
-            if chars.len() > j + 2 && chars[j + 2] == "'" {
-                let value = chars[j + 1].to_string();
-                tokens.push(Token { token_type: TokenType::SingleQuote, value: (value.chars().collect::<Vec<char>>()[0]).to_string() });
-                skip = 2;
-            }
-            else if chars.len() > j + 3 && chars[j + 1] == "\\" && chars[j + 3] == "'" {
-                let mut value = chars[j + 1].to_string();
-                value += chars[j + 2].to_string().as_str();
-                let c = match value.chars().collect::<Vec<char>>() {
-                    vec if vec.len() == 2 => match (vec[0], vec[1]) {
-                        ('\\', 'n') => '\n',
-                        ('\\', 't') => '\t',
-                        ('\\', 'r') => '\r',
-                        ('\\', '0') => '\0',
-                        ('\\', '\'') => '\'',
-                        ('\\', '\"') => '\"',
-                        ('\\', '\\') => '\\',
-                        _ => panic!("Invalid escape character"),
-                    },
-                    _ => panic!("Invalid escape character"),
-                };
-                tokens.push(Token { token_type: TokenType::SingleQuote, value: c.to_string() });
-                skip = 3; 
+
+        // chars
+        if c == '\'' {
+            if matches!(peek(i + 1), '\'' | '\n') {
+                panic!("Expected char quote to be one character in line {}", span.line);
             }
-            else {
-                panic!("Expected char quote to be one character");
+            let (value, length) = character(i + 1, span.line);
+            let length = length + 1;
+            if i + length >= chars.len() || chars[i + length].1 != '\'' {
+                panic!("Expected char quote to be one character in line {}", span.line);
             }
+            tokens.push(Token { token_type: TokenType::SingleQuote, value: value.to_string(), span });
+            i += length + 1;
+            continue;
         }
-        // symbols:
-        else if c == "]" { tokens.push(Token { token_type: TokenType::CloseBracket, value: c.to_string() }); single = true; }
-        else if c == "+" { tokens.push(Token { token_type: TokenType::Plus, value: c.to_string() }); single = true; }
-        else if c == "-" { tokens.push(Token { token_type: TokenType::Dash, value: c.to_string() }); single = true; }
-        else if c == "*" { tokens.push(Token { token_type: TokenType::Star, value: c.to_string() }); single = true; }
-        else if c == "(" { tokens.push(Token { token_type: TokenType::OpenParen, value: c.to_string() }); single = true; }
-        else if c == ")" { tokens.push(Token { token_type: TokenType::CloseParen, value: c.to_string() }); single = true; }
-        else if c == "{" { tokens.push(Token { token_type: TokenType::OpenCurley, value: c.to_string() }); single = true; }
-        else if c == "}" { tokens.push(Token { token_type: TokenType::CloseCurley, value: c.to_string() }); single = true; }
-        else if c == "," { tokens.push(Token { token_type: TokenType::Comma, value: c.to_string() }); single = true; }
-        else if c == "^" { tokens.push(Token { token_type: TokenType::XOR, value: c.to_string() }); single = true; }
-        // is alphabetical
-        else if is_alphabetical(c) || !alphabetical.is_empty() {
-            alphabetical += c;
-        }
-        // is number
-        else if let Ok(_) = parse_str_to_i32(c) {
-            number += c;
-        }
-        // spaces:
-        if c == " " || chars.len() == j + 1 || single {
-            let mut tok: Token = Token { token_type:TokenType::None, value:String::new() };
-            if single {
-                tok = tokens.last().unwrap().clone();
-                tokens.remove(tokens.len() - 1);
-            }
-            if !number.is_empty() {
-                tokens.push(Token { token_type: TokenType::Number, value: number.to_string() });
-                number = String::new();
-            }
-            if !alphabetical.trim().is_empty() {
-                match alphabetical.trim() {
-                    "uint8" | "char" | "bool" | "let" => tokens.push(Token { token_type: TokenType::TypeName, value: alphabetical.trim().to_string() }),
-                    "uint8[]" | "char[]" | "bool[]" => tokens.push(Token { token_type: TokenType::TypeName, value: alphabetical.trim().to_string() }),
-                    "if" | "while" => tokens.push(Token { token_type: TokenType::Statement, value: alphabetical.trim().to_string() }),
-                    "true" | "false" => tokens.push(Token { token_type: TokenType::Boolean, value: alphabetical.trim().to_string() }),
-                    _ => tokens.push(Token { token_type: TokenType::Identifier, value: alphabetical.trim().to_string() })
+
+        // strings
+        if c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while peek(i) != '"' {
+                if i >= chars.len() || peek(i) == '\n' {
+                    panic!("String is never closed in line {}", span.line);
                 }
-                alphabetical = String::new();
-            }
-            if single {
-                single = false;
-                tokens.push(tok);
+                let (c, length) = character(i, span.line);
+                value.push(c);
+                i += length;
             }
+            tokens.push(Token { token_type: TokenType::DoubleQuote, value, span });
+            i += 1;
+            continue;
         }
-    }
 
-    Line { tokens:tokens, number:line_number }
-}
-fn parse_str_to_i32(s: &str) -> Result<i32> {
-    match s.trim().parse::<i32>() {
-        Ok(num) => Ok(num),
-        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        // symbols, the longest one that matches
+        let next = peek(i + 1);
+        let (token_type, length) = match (c, next) {
+            ('=', '=') => (TokenType::EqualCompare, 2),
+            ('!', '=') => (TokenType::NotEqual, 2),
+            ('<', '=') => (TokenType::LessThanOrEqualTo, 2),
+            ('>', '=') => (TokenType::GreaterThanOrEqualTo, 2),
+            ('<', '<') => (TokenType::ShiftLeft, 2),
+            ('>', '>') => (TokenType::ShiftRight, 2),
+            ('&', '&') => (TokenType::AndAnd, 2),
+            ('|', '|') => (TokenType::OrOr, 2),
+            ('+', '+') => (TokenType::Increment, 2),
+            ('-', '-') => (TokenType::Decrement, 2),
+            ('=', _) => (TokenType::Equal, 1),
+            ('!', _) => (TokenType::Not, 1),
+            ('<', _) => (TokenType::LessThan, 1),
+            ('>', _) => (TokenType::GreaterThan, 1),
+            ('&', _) => (TokenType::And, 1),
+            ('|', _) => (TokenType::Or, 1),
+            ('+', _) => (TokenType::Plus, 1),
+            ('-', _) => (TokenType::Dash, 1),
+            ('*', _) => (TokenType::Star, 1),
+            ('/', _) => (TokenType::Slash, 1),
+            ('^', _) => (TokenType::XOR, 1),
+            ('(', _) => (TokenType::OpenParen, 1),
+            (')', _) => (TokenType::CloseParen, 1),
+            ('{', _) => (TokenType::OpenCurley, 1),
+            ('}', _) => (TokenType::CloseCurley, 1),
+            ('[', _) => (TokenType::OpenBracket, 1),
+            (']', _) => (TokenType::CloseBracket, 1),
+            (',', _) => (TokenType::Comma, 1),
+            (';', _) => (TokenType::Semicolon, 1),
+            _ => panic!("Unexpected character '{}' in line {} column {}", c, span.line, span.column),
+        };
+        let value: String = chars[i..i + length].iter().map(|x| x.1).collect();
+        tokens.push(Token { token_type, value, span });
+        i += length;
     }
+    tokens
 }
-fn is_alphabetical(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
-    }
-    if s.len() == 1 {
-        match s.parse::<char>() {
-            Ok(c) => return c.is_alphabetic(),
-            Err(_) => return false
-        };
+
+/// # Expr
+/// An expression, anything that has a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A number, char, string, `true` or `false`
+    Literal(Token),
+    Variable(Token),
+    /// The values an array is created with, `{ 1, 2, 3 }`
+    Array { token: Token, values: Vec<Expr> },
+    /// A call to a function like `print('a')`
+    Call { name: Token, arguments: Vec<Expr> },
+    /// An element of an array, `list[1]`
+    Index { array: Token, index: Box<Expr> },
+    /// Two values with an operator between them like `a + b` or `a && b`
+    Binary { op: Token, left: Box<Expr>, right: Box<Expr> },
+    /// `!` or `-` before a value, or `++` or `--` after one
+    Unary { op: Token, operand: Box<Expr> },
+    /// A value turned into another type, `(uint8)c`
+    Cast { type_name: Token, operand: Box<Expr> },
+}
+impl Expr {
+    /// returns the first token of the expression
+    pub fn token(&self) -> &Token {
+        match self {
+            Expr::Literal(token) | Expr::Variable(token) => token,
+            Expr::Array { token, .. } => token,
+            Expr::Call { name, .. } => name,
+            Expr::Index { array, .. } => array,
+            Expr::Binary { left, .. } => left.token(),
+            Expr::Unary { op, operand } => if is_postfix(op) { operand.token() } else { op },
+            Expr::Cast { type_name, .. } => type_name,
+        }
     }
-    else {
-        return false;
+    /// returns the line of code the expression starts in
+    pub fn line(&self) -> usize {
+        self.token().span.line
     }
 }
-#[derive(Debug, Clone)]
-pub struct ExprNode {
-    pub c: String,
-    pub token: Token,
-    pub line: i32,
-    pub operand1: Option<Box<ExprNode>>,
-    pub operand2: Option<Box<ExprNode>>,
-    pub func_parameters: Option<Vec<Vec<Option<ExprNode>>>>,
-    pub func_name: Option<String>,
-    pub set: Option<Vec<Vec<Option<ExprNode>>>>,
-    pub bracket_set: Option<Vec<Option<ExprNode>>>,
-    pub statement_insides: Option<Vec<Option<ExprNode>>>,
-    pub statement_lines: Option<Vec<ExprNode>>
+
+/// # Stmt
+/// A statement, the code between semicolons
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stmt {
+    /// Creates a variable, `uint8 x = 5` or `char[] list = { 'a', 'b' }`
+    Decl { type_name: Token, name: Token, value: Expr },
+    /// Creates an array with a number of elements and no values, `uint8 buf[8]`
+    Array { type_name: Token, name: Token, size: Expr },
+    /// Sets a variable, `x = 5`
+    Assign { target: Expr, value: Expr },
+    /// `else if` is an `If` by itself in `else_body`
+    If { token: Token, condition: Expr, body: Vec<Stmt>, else_body: Vec<Stmt> },
+    While { token: Token, condition: Expr, body: Vec<Stmt> },
+    /// `do { } while (condition);`, the body runs before the condition is checked
+    DoWhile { token: Token, body: Vec<Stmt>, condition: Expr },
+    /// `for (init; condition; step) { }`, any of the three can be left out
+    For { token: Token, init: Option<Box<Stmt>>, condition: Option<Expr>, step: Option<Box<Stmt>>, body: Vec<Stmt> },
+    /// Leaves the loop it is in
+    Break(Token),
+    /// Goes to the next time around the loop it is in
+    Continue(Token),
+    /// An expression by itself like `print('a')`
+    Expr(Expr),
+    /// `uint8 name(uint8 a, char b) { }`, the return type is `void` if the function has no value
+    Function { return_type: Token, name: Token, parameters: Vec<(Token, Token)>, body: Vec<Stmt> },
+    /// Leaves the function it is in, with a value unless the function is `void`
+    Return { token: Token, value: Option<Expr> },
+}
+
+fn is_postfix(op: &Token) -> bool {
+    op.token_type == TokenType::Increment || op.token_type == TokenType::Decrement
 }
 
-impl ExprNode {
-    fn is_func(&self) -> bool {
-        self.func_name.is_some() && self.func_parameters.is_some() 
+/// returns how tightly a binary operator holds on to its values, the same order as C. 0 if the token is not a binary operator
+pub fn precedence(token_type: &TokenType) -> u8 {
+    match token_type {
+        TokenType::OrOr => 1,
+        TokenType::AndAnd => 2,
+        TokenType::Or => 3,
+        TokenType::XOR => 4,
+        TokenType::And => 5,
+        TokenType::EqualCompare | TokenType::NotEqual => 6,
+        TokenType::GreaterThan | TokenType::GreaterThanOrEqualTo | TokenType::LessThan | TokenType::LessThanOrEqualTo => 7,
+        TokenType::ShiftLeft | TokenType::ShiftRight => 8,
+        TokenType::Plus | TokenType::Dash => 9,
+        TokenType::Star | TokenType::Slash => 10,
+        _ => 0,
     }
-    fn new_num(num: Token, line: i32) -> Self {
-        ExprNode {
-            c: num.value.clone(),
-            token: num,
-            line: line,
-            operand1: None,
-            operand2: None,
-            func_parameters: None,
-            func_name: None,
-            set: None,
-            bracket_set: None,
-            statement_insides: None,
-            statement_lines: None
+}
+
+/// # parse
+/// Parses tokens into statements. Constants are put in place of their name while parsing.
+/// ```text
+/// program    = { function | statement }
+/// function   = type name "(" [ type name { "," type name } ] ")" "{" { statement } "}"
+/// statement  = "const" name "=" literal ";"
+///            | "if" "(" expression ")" body [ "else" body ]
+///            | "while" "(" expression ")" body
+///            | "do" body "while" "(" expression ")" ";"
+///            | "for" "(" [ simple ] ";" [ expression ] ";" [ simple ] ")" body
+///            | ( "break" | "continue" ) ";"
+///            | "return" [ expression ] ";"
+///            | simple ";"
+///            | ";"
+/// simple     = type name "=" ( expression | "{" [ expression { "," expression } ] "}" | string )
+///            | type name "[" expression "]"
+///            | expression [ "=" expression ]
+/// body       = "{" { statement } "}" | statement
+/// expression = unary { operator unary }
+/// unary      = ( "!" | "-" | "(" type ")" ) unary | primary { "++" | "--" }
+/// primary    = literal | name | name "(" [ expression { "," expression } ] ")" | name "[" expression "]" | "(" expression ")"
+/// ```
+/// # Arguments
+/// * `tokens` - Tokens from `tokenize`
+/// # Returns
+/// * `Vec<Stmt>` - The statements of the program
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize, fmt_stmt, Stmt, Expr};
+/// let program = parse(&tokenize("const N = 2;\nuint8 x = 1 + N * 3;\nif (x > 5) {\n    print('a');\n}"));
+/// assert_eq!(fmt_stmt(&program[0]), "uint8 x = (1 + (2 * 3))");
+/// let cast = parse(&tokenize("char c = (char)((uint8)'a' + 1);"));
+/// assert_eq!(fmt_stmt(&cast[0]), "char c = (char)((uint8)'a' + 1)");
+/// let array = parse(&tokenize("uint8 buf[8];\nbuf[x] = 1;"));
+/// assert_eq!(fmt_stmt(&array[0]), "uint8 buf[8]");
+/// let text = parse(&tokenize("char[] s = \"hi\\n\";"));
+/// assert_eq!(fmt_stmt(&text[0]), "char[] s = \"hi\\n\"");
+/// match &program[1] {
+///     Stmt::If { condition: Expr::Binary { op, .. }, body, .. } => assert_eq!((op.value.as_str(), body.len()), (">", 1)),
+///     _ => panic!("expected an if statement"),
+/// }
+/// ```
+/// # Panics
+/// Will panic if the tokens don't follow the grammar
+pub fn parse(tokens: &[Token]) -> Vec<Stmt> {
+    let mut parser = Parser { tokens, position: 0, constants: vec![], loops: 0, function: false };
+    let mut program: Vec<Stmt> = vec![];
+    while parser.peek().is_some() {
+        // functions are only defined outside of other code, a type and name followed by '('
+        let is_function = tokens[parser.position..].iter().take(3).map(|x| &x.token_type)
+            .eq([TokenType::TypeName, TokenType::Identifier, TokenType::OpenParen].iter());
+        if is_function {
+            program.push(parser.function());
         }
-    }
-    fn new_op(op: Token, e1: ExprNode, e2: ExprNode, line: i32) -> Self {
-        ExprNode {
-            c: op.value.clone(),
-            token: op,
-            line: line,
-            operand1: Some(Box::new(e1)),
-            operand2: Some(Box::new(e2)),
-            func_parameters: None,
-            func_name: None,
-            set: None,
-            bracket_set: None,
-            statement_insides: None,
-            statement_lines: None
+        else {
+            program.extend(parser.statement());
         }
     }
-    fn new_func(func: Token, parameters: Option<Vec<Vec<Option<ExprNode>>>>, line: i32) -> Self {
-        ExprNode {
-            c: String::new(),
-            token: func.clone(),
-            line: line,
-            operand1: None,
-            operand2: None,
-            func_parameters: parameters,
-            func_name: Some(func.value.clone()),
-            set: None,
-            bracket_set: None,
-            statement_insides: None,
-            statement_lines: None
-        }
+    program
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    /// The name and value of every constant defined so far
+    constants: Vec<(String, Token)>,
+    /// How many loops the parser is inside of
+    loops: usize,
+    /// If the parser is inside a function, where `return` can be used
+    function: bool,
+}
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
     }
-    fn new_set(token: Token, indexes: Option<Vec<Vec<Option<ExprNode>>>>, line_num: i32) -> Self {
-        ExprNode {
-            c: String::new(),
-            token: token.clone(),
-            line: line_num,
-            operand1: None,
-            operand2: None,
-            func_parameters: None,
-            func_name: None,
-            set: indexes.clone(),
-            bracket_set: None,
-            statement_insides: None,
-            statement_lines: None
+    fn check(&self, token_type: &TokenType) -> bool {
+        self.peek().is_some_and(|x| x.token_type == *token_type)
+    }
+    /// skips the next token if it is the type given
+    fn eat(&mut self, token_type: TokenType) -> bool {
+        let found = self.check(&token_type);
+        if found {
+            self.position += 1;
         }
+        found
     }
-    fn new_bracket_set(token: Token, indexes: Option<Vec<Option<ExprNode>>>, line_num: i32) -> Self {
-        ExprNode {
-            c: String::new(),
-            token: token.clone(),
-            line: line_num,
-            operand1: None,
-            operand2: None,
-            func_parameters: None,
-            func_name: None,
-            set: None,
-            bracket_set: indexes.clone(),
-            statement_insides: None,
-            statement_lines: None
+    fn next(&mut self, expected: &str) -> &'a Token {
+        match self.peek() {
+            Some(token) => {
+                self.position += 1;
+                token
+            }
+            None => self.fail(expected),
         }
     }
-    fn new_statement(token: Token, insides: Vec<Option<ExprNode>>, lines: Vec<ExprNode>, line_num: i32) -> Self {
-        ExprNode {
-            c: String::new(),
-            token: token.clone(),
-            line: line_num,
-            operand1: None,
-            operand2: None,
-            func_parameters: None,
-            func_name: None,
-            set: None,
-            bracket_set: None,
-            statement_insides: Some(insides),
-            statement_lines: Some(lines)
+    fn expect(&mut self, token_type: TokenType, expected: &str) -> &'a Token {
+        if !self.check(&token_type) {
+            self.fail(expected);
         }
+        self.next(expected)
     }
-}
-
-pub fn precedence(op: String) -> i32 {
-    match op.as_str() {
-        "=" => 1,
-        "&&" | "||" => 2,
-        "&" | "|" | ">>" | "<<" => 3,
-        "==" | "!=" => 4,
-        ">" | "<" | ">=" | "<=" => 5,
-        "+" | "-" => 6,
-        "*" | "/" => 7,
-        "!" => 8,
-        _ => 0,
+    fn fail(&self, expected: &str) -> ! {
+        match self.peek() {
+            Some(token) => panic!("Expected {} but found '{}' in line {}", expected, token.value, token.span.line),
+            None => panic!("Expected {} after the end of the code in line {}", expected, self.tokens.last().map_or(1, |x| x.span.line)),
+        }
     }
-}
-pub fn parse(lexer_lines: Vec<Line>) -> Vec<Option<ExprNode>> {
-    let mut lines = lexer_lines.iter().peekable();
-    let mut returns: Vec<Option<ExprNode>> = Vec::new();
-
-    while let Some(l) = lines.peek() {
-        let mut chars = l.tokens.iter().peekable();
-        let mut last_was_digit_or_closing = false;
-        let mut last_was_variable = false;
-        let mut operator_stack: Vec<Token> = Vec::new();
-        let mut expr_stack: Vec<ExprNode> = Vec::new();
-        let number = l.number;
 
-        while let Some(c) = chars.peek() {
-            match c.token_type {
-                TokenType::OpenParen => {
-                    if last_was_digit_or_closing || last_was_variable {
-                        // Implicit multiplication: e.g., "2(" or ")("
-                        operator_stack.push(Token { value: "*".to_string(), token_type: TokenType::Star });
+    /// returns `None` for statements that only matter while parsing, like constants and empty statements
+    fn statement(&mut self) -> Option<Stmt> {
+        let token = self.peek()?;
+        let stmt = match token.token_type {
+            TokenType::Semicolon => None,
+            TokenType::Statement => match token.value.as_str() {
+                "else" => panic!("'else' without 'if' in line {}", token.span.line),
+                "break" | "continue" => {
+                    if self.loops == 0 {
+                        panic!("'{}' outside of a loop in line {}", token.value, token.span.line);
                     }
-                    operator_stack.push((*c).clone());
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::CloseParen => {
-                    while operator_stack.last().map_or(false, |op| op.token_type != TokenType::OpenParen) && expr_stack.len() > 1 {
-                        let operator = operator_stack.pop().unwrap();
-                        let e2 = expr_stack.pop().unwrap();
-                        let e1 = expr_stack.pop().unwrap();
-                        expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
+                    self.position += 1;
+                    match token.value.as_str() {
+                        "break" => Some(Stmt::Break(token.clone())),
+                        _ => Some(Stmt::Continue(token.clone())),
                     }
-                    operator_stack.pop(); // Pop the '('
-                    chars.next();
-                    last_was_digit_or_closing = true;
-                    last_was_variable = false;
                 }
-                TokenType::Number | TokenType::Boolean => {
-                    expr_stack.push(ExprNode::new_num((*c).clone(), number));
-                    chars.next();
-                    last_was_digit_or_closing = true;
-                    last_was_variable = false;
-                }
-                TokenType::SingleQuote => {
-                    expr_stack.push(ExprNode::new_num((*c).clone(), number));
-                    chars.next();
-                    last_was_digit_or_closing = true;
-                    last_was_variable = false;
-                }
-                TokenType::Dash => {
-                    if !last_was_digit_or_closing && !last_was_variable {
-                        chars.next();
-                        let operator = Token { value: "-".to_string(), token_type: TokenType::Dash };
-                        let e1 = ExprNode::new_num(Token { token_type: TokenType::Number, value: "0".to_string() }, number);
-                        let e2 = ExprNode::new_num(chars.peek().unwrap().clone().clone(), number);
-                        expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-                    } else {
-                        while operator_stack.last().map_or(false, |top| precedence(top.value.clone()) >= precedence(c.value.clone())) && expr_stack.len() > 1 {
-                            let operator = operator_stack.pop().unwrap();
-                            let e2 = expr_stack.pop().unwrap();
-                            let e1 = expr_stack.pop().unwrap();
-                            expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-                        }
-                        operator_stack.push((*c).clone());
-                    }
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Not => {
-                    chars.next();
-                    let operator = Token { value: "!".to_string(), token_type: TokenType::Not };
-                    let e1 = ExprNode::new_num(chars.peek().unwrap().clone().clone(), number);
-                    let e2 = ExprNode::new_num(Token { token_type: TokenType::None, value: "".to_string() }, number);
-                    expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Increment => {
-                    let operator = Token { value: "++".to_string(), token_type: TokenType::Increment };
-                    let e1 = expr_stack.pop().unwrap();
-                    let e2 = ExprNode::new_num(Token { token_type: TokenType::None, value: String::new() }, number);
-                    expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Decrement => {
-                    let operator = Token { value: "--".to_string(), token_type: TokenType::Decrement };
-                    let e1 = expr_stack.pop().unwrap();
-                    let e2 = ExprNode::new_num(Token { token_type: TokenType::None, value: String::new() }, number);
-                    expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Plus | TokenType::Star | TokenType::Slash | TokenType::GreaterThan | TokenType::GreaterThanOrEqualTo | TokenType::AndAnd | TokenType::EqualCompare | TokenType::XOR |
-                TokenType::LessThan | TokenType::LessThanOrEqualTo | TokenType::Comma | TokenType::NotEqual | TokenType::Equal | TokenType::OrOr | TokenType::Or | TokenType::And | TokenType::ShiftLeft | TokenType::ShiftRight => {
-                    while operator_stack.last().map_or(false, |top| precedence(top.value.clone()) >= precedence(c.value.clone())) && expr_stack.len() > 1 {
-                        let operator = operator_stack.pop().unwrap();
-                        let e2 = expr_stack.pop().unwrap();
-                        let e1 = expr_stack.pop().unwrap();
-                        expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-                    }
-                    operator_stack.push((*c).clone());
-                    chars.next();
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::TypeName => {
-                    let t_type = c.token_type.clone() ;
-                    let operator = Token { value: format!("{}", chars.next().unwrap().value.to_string()), token_type: t_type};
-                    let e1 = ExprNode::new_num(chars.next().unwrap().clone(), number);
-                    let e2 = ExprNode::new_num(Token { token_type: TokenType::None, value: String::new() }, number);
-                    expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
-
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Statement => {
-                    // Get all tokens inside the parentheses
-                    let mut tokens_inside_parenthesis: Vec<Token> = vec![];
-                    let mut parenthesis_count = 0;
-                    let name = (*c).clone();
-                    chars.next();
-
-                    while let Some(c) = chars.peek() {
-                        match c.token_type {
-                            TokenType::OpenParen => {
-                                parenthesis_count += 1;
-                                if parenthesis_count > 1 {
-                                    tokens_inside_parenthesis.push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            TokenType::CloseParen => {
-                                parenthesis_count -= 1;
-                                let mut breaks = false;
-                                if parenthesis_count == 0 {
-                                    breaks = true;
-                                }
-                                else {
-                                    tokens_inside_parenthesis.push((*c).clone());
-                                }
-                                chars.next();
-                                
-                                if breaks{
-                                    break;
-                                }
-                            }
-                            _ => {
-                                tokens_inside_parenthesis.push((*c).clone());
-                                chars.next();
-                            }
-                        }
-                    }
-                    
-                    let mut parsed_tokens: Vec<Option<ExprNode>> = Vec::new();
-                    if tokens_inside_parenthesis.len() != 0 {   
-                        parsed_tokens = parse(vec![Line { number: l.number, tokens: tokens_inside_parenthesis }]);
-                    }
-                    
-                    let mut curley_tokens_inside_parens: Vec<Vec<Token>> = vec![vec![]];
-                    let mut curley_index = 0;
-                    let mut curley_count = 0;
-
-                    while let Some(c) = chars.peek() {
-                        match c.token_type {
-                            TokenType::OpenCurley => {
-                                curley_count += 1;
-                                if curley_count > 1 {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            TokenType::CloseCurley => {
-                                curley_count -= 1;
-                                let mut breaks = false;
-                                if curley_count <= 0 {
-                                    breaks = true;
-                                }
-                                else {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                                
-                                if breaks{
-                                    break;
-                                }
-                            }
-                            TokenType::Comma => {
-                                if curley_count == 1 {
-                                    curley_tokens_inside_parens.push(vec![]);
-                                    curley_index += 1;
-                                }
-                                else {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            _ => {
-                                curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                chars.next();
-                            }
-                        }
-                    }
-
-                    let mut curley_parsed_tokens: Vec<Vec<Option<ExprNode>>> = Vec::new();
-                    if curley_tokens_inside_parens[0].len() != 0 {   
-                        for tokens_inside_parens in curley_tokens_inside_parens {
-                            curley_parsed_tokens.push(parse(vec![Line { number: l.number, tokens: tokens_inside_parens }]));
-                        }
+                "return" => {
+                    if !self.function {
+                        panic!("'return' outside of a function in line {}", token.span.line);
                     }
-                    
-                    let lines: Vec<ExprNode> = curley_parsed_tokens.iter().map(|x| x[0].clone().unwrap()).collect();
-
-                    expr_stack.push(ExprNode::new_statement(name, parsed_tokens, lines, number));
-
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::Identifier => {
-                    // Get all tokens inside the parentheses
-                    let mut params_tokens_inside_parens: Vec<Vec<Token>> = vec![vec![]];
-                    let mut params_index = 0;
-                    let mut parenthesis_count = 0;
-                    let mut index = 0;
-                    let name = (*c).clone();
-                    chars.next();
-
-                    while let Some(c) = chars.peek() {
-                        match c.token_type {
-                            TokenType::OpenParen => {
-                                index += 1;
-                                parenthesis_count += 1;
-                                if parenthesis_count > 1 {
-                                    params_tokens_inside_parens[params_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            TokenType::CloseParen => {
-                                if index == 0 {
-                                    break;
-                                }
-                                index += 1;
-                                parenthesis_count -= 1;
-                                let mut breaks = false;
-                                if parenthesis_count == 0 {
-                                    breaks = true;
-                                }
-                                else {
-                                    params_tokens_inside_parens[params_index].push((*c).clone());
-                                }
-                                chars.next();
-                                
-                                if breaks{
-                                    break;
-                                }
-                            }
-                            TokenType::Comma => {
-                                if index == 0 {
-                                    break;
-                                }
-                                index += 1;
-                                if parenthesis_count == 1 {
-                                    params_tokens_inside_parens.push(vec![]);
-                                    params_index += 1;
-                                }
-                                else {
-                                    params_tokens_inside_parens[params_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            _ => {
-                                if index == 0 {
-                                    break;
-                                }
-                                index += 1;
-                                params_tokens_inside_parens[params_index].push((*c).clone());
-                                chars.next();
-                            }
-                        }
-                    }
-
-                    if index == 0 {
-                        // is constant
-                        if last_was_digit_or_closing || last_was_variable {
-                            operator_stack.push(Token { value: "*".to_string(), token_type: TokenType::Star });
-                        }
-
-                        expr_stack.push(ExprNode::new_num(name.clone(), number));
-                        last_was_digit_or_closing = true;
-                        last_was_variable = true;
-                    }
-                    else {
-                        // is function
-                        let mut parsed_tokens: Vec<Vec<Option<ExprNode>>> = Vec::new();
-                        if params_tokens_inside_parens[0].len() != 0 {   
-                            for tokens_inside_parens in params_tokens_inside_parens {
-                                parsed_tokens.push(parse(vec![Line { number: l.number, tokens: tokens_inside_parens }]));
-                            }
-                        }
-                        
-                        expr_stack.push(ExprNode::new_func(name, Some(parsed_tokens), number));
-                        
-                        last_was_digit_or_closing = true;
-                        last_was_variable = false;
-                    }
-                }
-                TokenType::OpenCurley => {
-                    // Get all tokens inside the curleys
-                    let mut curley_tokens_inside_parens: Vec<Vec<Token>> = vec![vec![]];
-                    let mut curley_index = 0;
-                    let mut curley_count = 1;
-                    let token = (*c).clone();
-                    chars.next();
-
-                    while let Some(c) = chars.peek() {
-                        match c.token_type {
-                            TokenType::OpenCurley => {
-                                curley_count += 1;
-                                if curley_count > 1 {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            TokenType::CloseCurley => {
-                                curley_count -= 1;
-                                let mut breaks = false;
-                                if curley_count == 0 {
-                                    breaks = true;
-                                }
-                                else {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                                
-                                if breaks{
-                                    break;
-                                }
-                            }
-                            TokenType::Comma => {
-                                if curley_count == 1 {
-                                    curley_tokens_inside_parens.push(vec![]);
-                                    curley_index += 1;
-                                }
-                                else {
-                                    curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            _ => {
-                                curley_tokens_inside_parens[curley_index].push((*c).clone());
-                                chars.next();
-                            }
-                        }
-                    }
-
-                    let mut parsed_tokens: Vec<Vec<Option<ExprNode>>> = Vec::new();
-                    if curley_tokens_inside_parens[0].len() != 0 {
-                        for tokens_inside_parens in curley_tokens_inside_parens {
-                            parsed_tokens.push(parse(vec![Line { number: l.number, tokens: tokens_inside_parens }]));
-                        }
-                    }
-                    
-                    expr_stack.push(ExprNode::new_set(token, Some(parsed_tokens), number));
-                    
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::CloseCurley => {
-                    panic!("Unexpected character '{}', has '}}' without opening '{{' in line {}", c.value, number); 
-                }
-                TokenType::OpenBracket => {
-                    // Get all tokens inside the brackets
-                    let mut bracket_tokens_inside_parens: Vec<Token> = vec![];
-                    let mut bracket_count = 1;
-                    let token = (*c).clone();
-                    chars.next();
-
-                    while let Some(c) = chars.peek() {
-                        match c.token_type {
-                            TokenType::OpenBracket => {
-                                bracket_count += 1;
-                                if bracket_count > 1 {
-                                    bracket_tokens_inside_parens.push((*c).clone());
-                                }
-                                chars.next();
-                            }
-                            TokenType::CloseBracket => {
-                                bracket_count -= 1;
-                                let mut breaks = false;
-                                if bracket_count == 0 {
-                                    breaks = true;
-                                }
-                                else {
-                                    bracket_tokens_inside_parens.push((*c).clone());
-                                }
-                                chars.next();
-                                
-                                if breaks{
-                                    break;
-                                }
-                            }
-                            _ => {
-                                bracket_tokens_inside_parens.push((*c).clone());
-                                chars.next();
-                            }
-                        }
-                    }
-
-                    let mut parsed_tokens: Vec<Option<ExprNode>> = Vec::new();
-                    if bracket_tokens_inside_parens.len() != 0 {
-                        parsed_tokens = parse(vec![Line { number: l.number, tokens: bracket_tokens_inside_parens }]);
-                    }
-                    
-                    let mut node = ExprNode::new_bracket_set(token, Some(parsed_tokens), number);
-                    node.operand1 = Some(Box::new(expr_stack.pop().unwrap()));
-                    expr_stack.push(node);
-                    
-                    last_was_digit_or_closing = false;
-                    last_was_variable = false;
-                }
-                TokenType::CloseBracket => {
-                    panic!("Unexpected character '{}', has ']' without opening '[' in line {}", c.value, number); 
-                }
-                _ => {
-                    panic!("Unexpected token '{}' in line {}", c.value, number); // Error handling
+                    self.position += 1;
+                    let value = if self.check(&TokenType::Semicolon) { None } else { Some(self.expression()) };
+                    Some(Stmt::Return { token: token.clone(), value })
                 }
+                "do" => return Some(self.do_while()),
+                "for" => return Some(self.for_loop()),
+                _ => return Some(self.conditional()),
+            }
+            TokenType::Identifier if token.value == "const" => {
+                self.constant();
+                None
             }
+            _ => Some(self.simple()),
+        };
+        self.expect(TokenType::Semicolon, "';'");
+        stmt
+    }
+    /// parses a declaration, assignment or expression without the `;` after it
+    fn simple(&mut self) -> Stmt {
+        if self.check(&TokenType::TypeName) {
+            return self.declaration();
         }
-
-        let mut i = 0;
-        while let Some(operator) = operator_stack.pop() {
-            i += 1;
-            if i < 99 {
-                let mut e1 = ExprNode::new_num(Token { value: String::new(), token_type: TokenType::None }, number);
-                let mut e2 = ExprNode::new_num(Token { value: String::new(), token_type: TokenType::None }, number);
-                if !expr_stack.is_empty() {
-                    e2 = expr_stack.pop().unwrap();
-                }
-                if !expr_stack.is_empty() {
-                    e1 = expr_stack.pop().unwrap();
-                }
-                expr_stack.push(ExprNode::new_op(operator, e1, e2, number));
+        let target = self.expression();
+        if !self.eat(TokenType::Equal) {
+            return Stmt::Expr(target);
+        }
+        if !matches!(target, Expr::Variable(_) | Expr::Index { .. }) {
+            panic!("Can't assign a value to '{}' in line {}", fmt_expr(&target), target.line());
+        }
+        Stmt::Assign { target, value: self.expression() }
+    }
+    /// parses the statements between `{` and `}`, or a single statement without them
+    fn body(&mut self) -> Vec<Stmt> {
+        if !self.eat(TokenType::OpenCurley) {
+            return self.statement().into_iter().collect();
+        }
+        let mut body: Vec<Stmt> = vec![];
+        while !self.eat(TokenType::CloseCurley) {
+            if self.peek().is_none() {
+                self.fail("'}'");
             }
+            body.extend(self.statement());
         }
-
-        returns.push(expr_stack.pop());
-        lines.next();
+        body
     }
-    returns.iter().filter(|x| x.is_some()).map(|x| x.clone()).collect::<Vec<Option<ExprNode>>>()
-}
-
-pub fn fmt_expr(node: &ExprNode) -> String {
-    if node.func_name.is_some() && node.func_parameters.is_some() {
-        let mut func_params: String = String::new();
-        for n in node.func_parameters.as_ref().unwrap() {
-            for o in n.iter() {
-                if let Some(p) = o.as_ref() {
-                    func_params = format!("{}, {}", func_params, fmt_expr(p));
+    fn constant(&mut self) {
+        self.next("'const'");
+        let name = self.expect(TokenType::Identifier, "the name of the constant");
+        self.expect(TokenType::Equal, "'='");
+        let value = self.next("the value of the constant");
+        if !matches!(value.token_type, TokenType::Number | TokenType::SingleQuote | TokenType::Boolean) {
+            panic!("Constant '{}' must be a number, char or bool in line {}", name.value, name.span.line);
+        }
+        self.constants.retain(|x| x.0 != name.value);
+        self.constants.push((name.value.clone(), value.clone()));
+    }
+    fn declaration(&mut self) -> Stmt {
+        let type_name = self.next("a type").clone();
+        let name = self.expect(TokenType::Identifier, "the name of the variable").clone();
+        if self.check(&TokenType::OpenParen) {
+            panic!("Function '{}' can only be defined outside of other code in line {}", name.value, name.span.line);
+        }
+        if !type_name.value.ends_with("[]") && self.eat(TokenType::OpenBracket) {
+            let size = self.expression();
+            self.expect(TokenType::CloseBracket, "']'");
+            return Stmt::Array { type_name, name, size };
+        }
+        self.expect(TokenType::Equal, "'='");
+        let value = if type_name.value.ends_with("[]") && !self.check(&TokenType::DoubleQuote) {
+            let token = self.expect(TokenType::OpenCurley, "'{' or a string with the values of the array").clone();
+            Expr::Array { token, values: self.list(TokenType::CloseCurley, "',' or '}'") }
+        }
+        else {
+            self.expression()
+        };
+        Stmt::Decl { type_name, name, value }
+    }
+    fn function(&mut self) -> Stmt {
+        let return_type = self.next("a type").clone();
+        let name = self.next("the name of the function").clone();
+        self.expect(TokenType::OpenParen, "'('");
+        let mut parameters: Vec<(Token, Token)> = vec![];
+        // `(void)` is the same as `()`
+        if self.peek().is_some_and(|x| x.value == "void") && self.tokens.get(self.position + 1).is_some_and(|x| x.token_type == TokenType::CloseParen) {
+            self.position += 1;
+        }
+        if !self.eat(TokenType::CloseParen) {
+            loop {
+                let type_name = self.expect(TokenType::TypeName, "the type of the parameter").clone();
+                let parameter = self.expect(TokenType::Identifier, "the name of the parameter").clone();
+                parameters.push((type_name, parameter));
+                if self.eat(TokenType::CloseParen) {
+                    break;
                 }
+                self.expect(TokenType::Comma, "',' or ')'");
             }
         }
+        if !self.check(&TokenType::OpenCurley) {
+            self.fail("'{'");
+        }
+        self.function = true;
+        let body = self.body();
+        self.function = false;
+        Stmt::Function { return_type, name, parameters, body }
+    }
+    /// parses `if` and `while`
+    fn conditional(&mut self) -> Stmt {
+        let token = self.next("'if' or 'while'").clone();
+        self.expect(TokenType::OpenParen, "'('");
+        let condition = self.expression();
+        self.expect(TokenType::CloseParen, "')'");
+        if token.value == "while" {
+            return Stmt::While { token, condition, body: self.loop_body() };
+        }
 
-        return format!("{}({})", node.func_name.as_ref().unwrap(), func_params.trim_start_matches(", "));
+        let body = self.body();
+        let mut else_body: Vec<Stmt> = vec![];
+        if self.check(&TokenType::Statement) && self.peek().is_some_and(|x| x.value == "else") {
+            self.position += 1;
+            else_body = self.body();
+        }
+        Stmt::If { token, condition, body, else_body }
+    }
+    fn do_while(&mut self) -> Stmt {
+        let token = self.next("'do'").clone();
+        let body = self.loop_body();
+        match self.peek() {
+            Some(x) if x.token_type == TokenType::Statement && x.value == "while" => self.position += 1,
+            _ => self.fail("'while'"),
+        }
+        self.expect(TokenType::OpenParen, "'('");
+        let condition = self.expression();
+        self.expect(TokenType::CloseParen, "')'");
+        self.expect(TokenType::Semicolon, "';'");
+        Stmt::DoWhile { token, body, condition }
+    }
+    fn for_loop(&mut self) -> Stmt {
+        let token = self.next("'for'").clone();
+        self.expect(TokenType::OpenParen, "'('");
+        let init = if self.check(&TokenType::Semicolon) { None } else { Some(Box::new(self.simple())) };
+        self.expect(TokenType::Semicolon, "';'");
+        let condition = if self.check(&TokenType::Semicolon) { None } else { Some(self.expression()) };
+        self.expect(TokenType::Semicolon, "';'");
+        let step = if self.check(&TokenType::CloseParen) { None } else { Some(Box::new(self.simple())) };
+        self.expect(TokenType::CloseParen, "')'");
+        Stmt::For { token, init, condition, step, body: self.loop_body() }
+    }
+    /// parses the body of a loop, where `break` and `continue` can be used
+    fn loop_body(&mut self) -> Vec<Stmt> {
+        self.loops += 1;
+        let body = self.body();
+        self.loops -= 1;
+        body
+    }
+    /// parses expressions separated by commas up to the closing token
+    fn list(&mut self, close: TokenType, expected: &str) -> Vec<Expr> {
+        let mut values: Vec<Expr> = vec![];
+        if self.eat(close.clone()) {
+            return values;
+        }
+        loop {
+            values.push(self.expression());
+            if self.eat(close.clone()) {
+                return values;
+            }
+            self.expect(TokenType::Comma, expected);
+        }
     }
 
-    if node.set.is_some() {
-        let mut set_curley: String = String::new();
-        for n in node.set.as_ref().unwrap() {
-            for o in n.iter() {
-                if let Some(p) = o.as_ref() {
-                    set_curley = format!("{}, {}", set_curley, fmt_expr(p));
-                }
+    fn expression(&mut self) -> Expr {
+        self.binary(1)
+    }
+    /// parses operators that hold on at least as tightly as `min_precedence`
+    fn binary(&mut self, min_precedence: u8) -> Expr {
+        let mut left = self.unary();
+        while let Some(op) = self.peek() {
+            let op_precedence = precedence(&op.token_type);
+            if op_precedence == 0 || op_precedence < min_precedence {
+                break;
             }
+            self.position += 1;
+            let right = self.binary(op_precedence + 1);
+            left = Expr::Binary { op: op.clone(), left: Box::new(left), right: Box::new(right) };
         }
-        
-        return format!("{{ {} }}", set_curley.trim_start_matches(", "));
+        left
     }
-    if node.bracket_set.is_some() {
-        let mut set_braces: String = String::new();
-        for n in node.bracket_set.as_ref().unwrap() {
-            if let Some(p) = n.as_ref() {
-                set_braces = format!("{}, {}", set_braces, fmt_expr(p));
+    fn unary(&mut self) -> Expr {
+        if self.check(&TokenType::Not) || self.check(&TokenType::Dash) {
+            let op = self.next("'!' or '-'").clone();
+            return Expr::Unary { op, operand: Box::new(self.unary()) };
+        }
+        // a type between parentheses is a cast
+        let is_cast = self.tokens[self.position..].iter().take(3).map(|x| &x.token_type)
+            .eq([TokenType::OpenParen, TokenType::TypeName, TokenType::CloseParen].iter());
+        if is_cast {
+            let type_name = self.tokens[self.position + 1].clone();
+            self.position += 3;
+            return Expr::Cast { type_name, operand: Box::new(self.unary()) };
+        }
+        let mut expr = self.primary();
+        while self.check(&TokenType::Increment) || self.check(&TokenType::Decrement) {
+            let op = self.next("'++' or '--'").clone();
+            expr = Expr::Unary { op, operand: Box::new(expr) };
+        }
+        expr
+    }
+    fn primary(&mut self) -> Expr {
+        let token = self.next("a value").clone();
+        match token.token_type {
+            TokenType::Number | TokenType::SingleQuote | TokenType::DoubleQuote | TokenType::Boolean => Expr::Literal(token),
+            TokenType::OpenParen => {
+                let expr = self.expression();
+                self.expect(TokenType::CloseParen, "')'");
+                expr
+            }
+            TokenType::Identifier => {
+                if let Some((_, value)) = self.constants.iter().find(|x| x.0 == token.value) {
+                    // the constant keeps the place it is used at
+                    return Expr::Literal(Token { span: token.span, ..value.clone() });
+                }
+                if self.eat(TokenType::OpenParen) {
+                    Expr::Call { name: token, arguments: self.list(TokenType::CloseParen, "',' or ')'") }
+                }
+                else if self.eat(TokenType::OpenBracket) {
+                    let index = self.expression();
+                    self.expect(TokenType::CloseBracket, "']'");
+                    Expr::Index { array: token, index: Box::new(index) }
+                }
+                else {
+                    Expr::Variable(token)
+                }
+            }
+            _ => {
+                self.position -= 1;
+                self.fail("a value")
             }
         }
-        
-        return format!("{}[{}]", node.operand1.as_ref().unwrap().token.value.clone(), set_braces.trim_start_matches(", "));
     }
+}
 
-    if node.statement_insides.is_some() || node.statement_lines.is_some() {
-        let st = fmt_expr(node.statement_insides.clone().unwrap()[0].as_ref().unwrap());
-        let set = fmt_expr(Some(ExprNode {
-            token: node.token.clone(),
-            line: node.line.clone(),
-            c: node.c.clone(),
-            func_name: None,
-            func_parameters: None,
-            operand1: None,
-            operand2: None,
-            set: Some(vec![node.statement_lines.clone().unwrap().iter().map(|n| Some(n.clone())).collect::<Vec<_>>()]),
-            bracket_set: None,
-            statement_insides: None,
-            statement_lines: None,
-        }).as_ref().unwrap());
-        
-        return format!("{} ({}) {}", node.token.value, st, set);
+/// # fmt_expr
+/// Writes an expression as code. Every binary operator is put in parentheses to show the order it runs in
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize, fmt_expr, Stmt};
+/// let program = parse(&tokenize("out(a + b * c == 1 && !list[0]);"));
+/// let Stmt::Expr(expr) = &program[0] else { panic!() };
+/// assert_eq!(fmt_expr(expr), "out((((a + (b * c)) == 1) && !list[0]))");
+/// ```
+pub fn fmt_expr(expr: &Expr) -> String {
+    let list = |values: &[Expr]| values.iter().map(fmt_expr).collect::<Vec<String>>().join(", ");
+    match expr {
+        Expr::Literal(token) if token.token_type == TokenType::SingleQuote => format!("'{}'", token.value.escape_default()),
+        Expr::Literal(token) if token.token_type == TokenType::DoubleQuote => format!("\"{}\"", token.value.escape_default()),
+        Expr::Literal(token) | Expr::Variable(token) => token.value.clone(),
+        Expr::Array { values, .. } => format!("{{ {} }}", list(values)),
+        Expr::Call { name, arguments } => format!("{}({})", name.value, list(arguments)),
+        Expr::Index { array, index } => format!("{}[{}]", array.value, fmt_expr(index)),
+        Expr::Binary { op, left, right } => format!("({} {} {})", fmt_expr(left), op.value, fmt_expr(right)),
+        Expr::Unary { op, operand } if is_postfix(op) => format!("{}{}", fmt_expr(operand), op.value),
+        Expr::Unary { op, operand } => format!("{}{}", op.value, fmt_expr(operand)),
+        Expr::Cast { type_name, operand } => format!("({}){}", type_name.value, fmt_expr(operand)),
     }
-        
-    if node.operand1.is_none() && node.operand2.is_none() {
-        if node.token.token_type == TokenType::SingleQuote {
-            return format!("'{}'", node.c);
+}
+/// # fmt_stmt
+/// Writes a statement as code, see `fmt_expr`. Statements with a block are written over several lines
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{parse, tokenize, fmt_stmt};
+/// let program = parse(&tokenize("while (x < 5) { x = x + 1; if (x == 3) { print('3'); } }"));
+/// assert_eq!(fmt_stmt(&program[0]), "while ((x < 5)) {\n    x = (x + 1);\n    if ((x == 3)) {\n        print('3');\n    }\n}");
+/// let program = parse(&tokenize("if (x == 1) print('a'); else if (x == 2) { print('b'); } else { print('c'); }"));
+/// assert_eq!(fmt_stmt(&program[0]), "if ((x == 1)) {\n    print('a');\n} else if ((x == 2)) {\n    print('b');\n} else {\n    print('c');\n}");
+/// let program = parse(&tokenize("for (uint8 i = 0; i < 3; i++) { if (i == 1) continue; do { break; } while (true); }"));
+/// assert_eq!(fmt_stmt(&program[0]), "for (uint8 i = 0; (i < 3); i++) {\n    if ((i == 1)) {\n        continue;\n    }\n    do {\n        break;\n    } while (true);\n}");
+/// ```
+pub fn fmt_stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Decl { type_name, name, value } => format!("{} {} = {}", type_name.value, name.value, fmt_expr(value)),
+        Stmt::Array { type_name, name, size } => format!("{} {}[{}]", type_name.value, name.value, fmt_expr(size)),
+        Stmt::Assign { target, value } => format!("{} = {}", fmt_expr(target), fmt_expr(value)),
+        Stmt::If { token, condition, body, else_body } => {
+            let code = format!("{} ({}) {}", token.value, fmt_expr(condition), fmt_block(body));
+            match else_body.as_slice() {
+                [] => code,
+                [Stmt::If { .. }] => format!("{code} else {}", fmt_stmt(&else_body[0])),
+                _ => format!("{code} else {}", fmt_block(else_body)),
+            }
+        }
+        Stmt::While { token, condition, body } => format!("{} ({}) {}", token.value, fmt_expr(condition), fmt_block(body)),
+        Stmt::DoWhile { token, body, condition } => format!("{} {} while ({})", token.value, fmt_block(body), fmt_expr(condition)),
+        Stmt::For { token, init, condition, step, body } => {
+            let init = init.as_ref().map_or(String::new(), |x| fmt_stmt(x));
+            let condition = condition.as_ref().map_or(String::new(), fmt_expr);
+            let step = step.as_ref().map_or(String::new(), |x| fmt_stmt(x));
+            format!("{} ({}; {}; {}) {}", token.value, init, condition, step, fmt_block(body))
         }
-        return node.c.to_string();
+        Stmt::Break(token) | Stmt::Continue(token) => token.value.clone(),
+        Stmt::Expr(expr) => fmt_expr(expr),
+        Stmt::Function { return_type, name, parameters, body } => {
+            let parameters: Vec<String> = parameters.iter().map(|(type_name, name)| format!("{} {}", type_name.value, name.value)).collect();
+            format!("{} {}({}) {}", return_type.value, name.value, parameters.join(", "), fmt_block(body))
+        }
+        Stmt::Return { token, value: Some(value) } => format!("{} {}", token.value, fmt_expr(value)),
+        Stmt::Return { token, value: None } => token.value.clone(),
     }
-
-    let left = node.operand1.as_ref().map_or("".to_string(), |n| fmt_expr(n));
-    let right = node.operand2.as_ref().map_or("".to_string(), |n| fmt_expr(n));
-
-    format!("({}{}{})", left, node.c, right)
 }
-
+fn fmt_block(body: &[Stmt]) -> String {
+    let mut code = String::from("{\n");
+    for stmt in body {
+        let line = match stmt {
+            Stmt::If { .. } | Stmt::While { .. } | Stmt::For { .. } | Stmt::Function { .. } => fmt_stmt(stmt),
+            _ => format!("{};", fmt_stmt(stmt)),
+        };
+        for line in line.lines() {
+            code += format!("    {line}\n").as_str();
+        }
+    }
+    code + "}"
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VariableType {
@@ -1023,173 +881,650 @@ impl Variable {
     }
 }
 
-pub fn interpret(lines: Vec<Option<ExprNode>>) -> String {
-    let mut variables: Vec<Variable> = vec![];
-    let lines_binding = lines.clone();
-    let lines= lines_binding.iter().cloned();
-    let mut result = String::new();
-    let mut bytes = 0;
+/// # Function
+/// A function defined in the code and where it was placed
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// `None` for `void` functions
+    pub return_type: Option<VariableType>,
+    /// The variables the arguments are stored in before the function is called
+    pub parameters: Vec<Variable>,
+    /// The address of the first instruction of the function
+    pub address: i32,
+    /// The address of the `JMP` that goes back to the caller. The caller stores where to go back to in its second byte
+    pub return_address: i32,
+}
 
-    let mut virtual_registers: [Byte; 4] = [Byte::zero(), Byte::zero(), Byte::zero(), Byte::zero()];
-    for (_, line) in lines.enumerate() {
-        if line.is_none() {
-            continue;
+/// # CompileOptions
+/// Choices for how the code is compiled
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Stops the program with `HALT` when an array index that isn't constant is outside of the array
+    pub bounds_checks: bool,
+}
+
+/// # Context
+/// Everything the compiler has made so far that later code can use
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The variables created so far, new ones are added to the end
+    pub variables: Vec<Variable>,
+    /// The functions defined so far
+    pub functions: Vec<Function>,
+    /// The index of the function being made, if any
+    function: Option<usize>,
+    /// How many values are held in memory while other code runs
+    held: usize,
+    /// How many labels have been made, every label has a number so its name is only used once
+    labels: usize,
+    options: CompileOptions,
+}
+impl Context {
+    /// returns the address of a variable to hold a value in. It is free until `release` is called.
+    /// A function has its own, so a call can't change a value its caller is holding
+    fn hold(&mut self) -> Byte {
+        let name = match self.function {
+            Some(index) => format!("$held{}@{}", self.held, self.functions[index].name),
+            None => format!("$held{}", self.held),
+        };
+        self.held += 1;
+        if let Some(variable) = self.variables.iter().rev().find(|x| x.name == name) {
+            return variable.address;
         }
-        if let Some(line) = line {
-            //result += format!("; {}\n", line.token.value).as_str();
-            let contents = solve_node(&line, &mut variables, "R0", &mut virtual_registers, VariableType::None, &mut bytes);
-            result += contents.clone().as_str().trim();
-            
-            let assembly_to_binary = &compile_assembly_to_binary(contents.as_str());
-            let bytes_vec = string_to_bytes(assembly_to_binary);
-            bytes += bytes_vec.len() as i32;
-            result += format!(" ; BYTE ADDRESS {bytes}\n").as_str();
+        let address = next_address(&self.variables);
+        self.variables.push(Variable::new(name, address, VariableType::None, false));
+        address
+    }
+    fn release(&mut self) {
+        self.held -= 1;
+    }
+    /// returns a new label for an address that isn't known until the code is placed
+    fn label(&mut self, name: &str) -> String {
+        self.labels += 1;
+        format!("{name}_{}", self.labels - 1)
+    }
+    /// renames the variables created since `first` to `name@scope` so the code after them can't use them.
+    /// Values held in memory are shared by the code outside of functions, so they keep their names
+    fn hide(&mut self, first: usize, scope: &str) {
+        for variable in &mut self.variables[first..] {
+            if variable.name.contains('@') || variable.name.starts_with('$') {
+                continue;
+            }
+            variable.name = match variable.name.split_once('|') {
+                Some((array, index)) => format!("{array}@{scope}|{index}"),
+                None => format!("{}@{scope}", variable.name),
+            };
         }
     }
+}
 
-    result += "HALT";
-    result
+pub fn interpret(program: &[Stmt]) -> String {
+    interpret_with_variables(program).0
 }
-pub fn solve_node(node: &ExprNode, variables: &mut Vec<Variable>, register: &str, virtual_registers: &mut [Byte; 4], expected_value: VariableType, bytes: &mut i32) -> String {
-    match node.token.token_type {
-        TokenType::Equal => {
-            if node.operand1.clone().unwrap().token.token_type == TokenType::TypeName {
-                let var_type = node.operand1.as_ref().unwrap();
-                let var_name = var_type.operand1.as_ref().unwrap();
+/// # interpret_with_variables
+/// Turns the parsed statements into assembly, and returns the variables with the address each was placed at.
+/// Once code after them can't use them, the variables of a function are named `name@function` and
+/// the variables created inside a statement like `if` are named `name@line` with the line the statement starts in.
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize("uint8 twice(uint8 n) { return n * 2; }\nuint8 x = twice(4);")));
+/// assert!(assembly.contains("JMP 2 ; call twice"));
+/// let names: Vec<&str> = variables.iter().map(|x| x.name.as_str()).collect();
+/// assert_eq!(names, ["n@twice", "x"]);
+/// ```
+/// A value held while a function runs keeps its value, even when the function holds values of its own
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "uint8 a = 1 + (2 * 3);\nuint8 f(uint8 n) { return n + (n * 2); }\nuint8 x = 5 + f(1);";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let x = variables.iter().find(|x| x.name == "x").unwrap();
+/// assert_eq!(computer.ram.read(x.address).to_i32(), 8);
+/// ```
+/// # Panics
+/// Will panic with every error `c_check::check` finds before any assembly is made
+pub fn interpret_with_variables(program: &[Stmt]) -> (String, Vec<Variable>) {
+    interpret_with_options(program, &CompileOptions::default())
+}
+/// # interpret_with_options
+/// Turns the parsed statements into assembly like `interpret_with_variables`, with options
+/// # Examples
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_options, parse, tokenize, CompileOptions};
+/// let program = parse(&tokenize("uint8 list[4];\nuint8 i = 4;\nlist[i] = 1;"));
+/// let options = CompileOptions { bounds_checks: true };
+/// let (assembly, _) = interpret_with_options(&program, &options);
+/// assert!(assembly.contains("HALT ; index outside of the array"));
+/// ```
+/// # Panics
+/// Will panic with every error `c_check::check` finds before any assembly is made
+pub fn interpret_with_options(program: &[Stmt], options: &CompileOptions) -> (String, Vec<Variable>) {
+    let errors = c_check::check(program);
+    if !errors.is_empty() {
+        panic!("{}", errors.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("\n"));
+    }
 
-                let v_type = match var_type.token.value.clone().as_str() {
-                    "bool" | "bool[]" => VariableType::Bool,
-                    "char" | "char[]" => VariableType::Char,
-                    "uint8" | "uint8[]" => VariableType::UInt8,
-                    "let" => VariableType::None,
-                    _ => panic!("Invalid variable type")
-                };
+    let mut context = Context { options: options.clone(), ..Context::default() };
+    let mut result = String::new();
+    let mut bytes = 0;
 
-                // Handle array type declaration
-                if var_type.operand2.clone().is_some() && var_type.token.clone().value.ends_with("[]") {
+    for stmt in program {
+        let contents = solve_stmt(stmt, &mut context, bytes);
+        result += contents.trim();
+        bytes += assembled_size(&contents);
+        result += format!(" ; BYTE ADDRESS {bytes}\n").as_str();
+    }
 
-                    // Parse the array values
-                    if let Some(array_values_node) = node.operand2.clone() {
-                        let array_values = (*array_values_node).set.unwrap();
+    result += "HALT";
+    (result, context.variables)
+}
+/// returns the number of bytes assembly code takes
+fn assembled_size(assembly: &str) -> i32 {
+    string_to_bytes(&compile_assembly_to_binary(assembly)).len() as i32
+}
+/// returns the address for the next variable, they are placed from the end of memory
+fn next_address(variables: &[Variable]) -> Byte {
+    (crate::vc_8bit::MAXBYTE - (variables.len() as i32 + 1)).try_into().unwrap()
+}
+fn find_variable<'a>(variables: &'a [Variable], name: &Token) -> &'a Variable {
+    // the newest variable with the name is used
+    match variables.iter().rev().find(|x| x.name == name.value) {
+        Some(variable) => variable,
+        None => panic!("Variable '{}' does not exist in line {}", name.value, name.span.line),
+    }
+}
+/// returns the value of a number known when compiling, as binary
+fn constant_byte(expr: &Expr, what: &str) -> String {
+    match expr {
+        Expr::Literal(token) if token.token_type == TokenType::Number => Byte::from_string(token.value.clone()).to_string(),
+        _ => panic!("{} requires constant value in line {}", what, expr.line()),
+    }
+}
 
-                        // Allocate space for the array in memory
-                        let mut assembly = String::new();
-                        for (i, value_node) in array_values.iter().enumerate() {
-                            let value = solve_node(value_node.iter().nth(0).unwrap().as_ref().unwrap(), variables, "R0", virtual_registers, v_type, bytes);
-                            let address = (crate::vc_8bit::MAXBYTE - (variables.len() as i32 + 1)).try_into().unwrap();
-                            variables.push(Variable::new(format!("{}|{}", var_name.token.value, i), address, v_type.clone(), true));
-                            assembly += format!("{}\nSTR R0 #{} ; store array element {}\n", value, address.to_string(), i).as_str();
-                        }
+/// adds the elements of an array next to each other without names, and returns the index of the first one
+fn reserve_array(context: &mut Context, var_type: VariableType, length: usize) -> usize {
+    let first = context.variables.len();
+    for _ in 0..length {
+        let address = next_address(&context.variables);
+        context.variables.push(Variable::new(String::new(), address, var_type, true));
+    }
+    first
+}
+/// names the elements of an array `name|index` once the array can be used
+fn name_array(context: &mut Context, first: usize, name: &str) {
+    for (i, element) in context.variables[first..].iter_mut().filter(|x| x.is_array && x.name.is_empty()).enumerate() {
+        element.name = format!("{name}|{i}");
+    }
+}
+/// returns the address of the first element and the number of elements of the newest array with the name.
+/// Element `i` is at the address of the first element minus `i`
+fn find_array(variables: &[Variable], array: &Token) -> (Byte, usize) {
+    let first = match variables.iter().rposition(|x| x.name == format!("{}|0", array.value)) {
+        Some(first) => first,
+        None => panic!("Variable '{}' is not an array in line {}", array.value, array.span.line),
+    };
+    let length = variables[first..].iter().enumerate().take_while(|(i, x)| x.name == format!("{}|{}", array.value, i)).count();
+    (variables[first].address, length)
+}
 
-                        return assembly;
-                    } else {
-                        panic!("Array must have initialization values");
-                    }
+/// # solve_element
+/// Loads or stores an element of an array at an index worked out while running. The VC only has instructions with
+/// the address in them, so the address of the element is stored in the second byte of the instruction before it runs
+/// # Arguments
+/// * `instruction` - `LDR` or `STR`
+/// * `register` - The register to load or store
+/// * `index` - The register the index is in
+/// * `other` - A register that can be changed
+/// * `base`, `length` - The address of the first element and the number of elements, see `find_array`
+fn solve_element(context: &mut Context, instruction: &str, register: &str, index: &str, other: &str, base: Byte, length: usize) -> String {
+    let mut assembly = String::new();
+    if context.options.bounds_checks {
+        let in_range = context.label("in_range");
+        assembly += format!("MOV {other} #{}\nSUB {other} {index} ; check the index is less than {length}\nJMP_ABV {in_range}\nHALT ; index outside of the array\n{in_range}:\n", Byte::from_u8(length as u8).to_string()).as_str();
+    }
+    let label = context.label("element");
+    let opcode = isa::lookup(instruction).unwrap().encode(&[get_index_from_register(register) as i32, 0])[0];
+    assembly += format!("MOV {other} #{}\nSUB {other} {index} ; address of the element\nSTR {other} {label} ; set the address of the instruction below\n", base.to_string()).as_str();
+    assembly += format!("%BYTE {opcode} ; {instruction} {register} with the address of the element\n{label}: %BYTE 0").as_str();
+    assembly
+}
+
+/// # solve_stmt
+/// Turns a statement into assembly
+/// # Arguments
+/// * `stmt` - The statement
+/// * `context` - The variables and functions created so far, new ones are added to the end
+/// * `address` - The address the assembly will be placed at, used for jumps
+/// # Examples
+/// Exactly one branch of an `if`, `else if` and `else` chain runs
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "uint8 runs = 0;
+/// uint8 a = 0; uint8 b = 0; uint8 c = 0;
+/// if (a == 0) { a = 10; runs++; } else if (a == 0) { a = 20; runs++; } else { a = 30; runs++; }
+/// if (b == 1) { b = 10; runs++; } else if (b == 0) { b = 20; runs++; } else { b = 30; runs++; }
+/// if (c == 1) { c = 10; runs++; } else if (c == 2) { c = 20; runs++; } else { c = 30; runs++; }";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let value = |name: &str| computer.ram.read(variables.iter().find(|x| x.name == name).unwrap().address).to_i32();
+/// assert_eq!((value("a"), value("b"), value("c"), value("runs")), (10, 20, 30, 3));
+/// ```
+/// `continue` skips to the `step` of a `for` loop, `break` leaves it, and `do while` runs before checking its condition
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "uint8 sum = 0;
+/// for (uint8 i = 0; i < 10; i++) {
+///     if (i == 2) continue;
+///     if (i == 6) break;
+///     sum = sum + i;
+/// }
+/// uint8 d = 0;
+/// do { d++; } while (d < 5);
+/// uint8 once = 0;
+/// do { once++; } while (false);";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let value = |name: &str| computer.ram.read(variables.iter().find(|x| x.name == name).unwrap().address).to_i32();
+/// assert_eq!((value("sum"), value("d"), value("once")), (0 + 1 + 3 + 4 + 5, 5, 1));
+/// ```
+/// `break` and `continue` in an inner loop only change the inner loop
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "uint8 pairs = 0;
+/// uint8 outer = 0;
+/// for (uint8 x = 0; x < 4; x++) {
+///     if (x == 3) break;
+///     uint8 y = 0;
+///     while (y < 4) {
+///         y++;
+///         if (y == 2) continue;
+///         if (y == 4) break;
+///         pairs++;
+///     }
+///     if (x == 1) continue;
+///     outer++;
+/// }";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let value = |name: &str| computer.ram.read(variables.iter().find(|x| x.name == name).unwrap().address).to_i32();
+/// assert_eq!((value("pairs"), value("outer")), (6, 2));
+/// ```
+pub fn solve_stmt(stmt: &Stmt, context: &mut Context, address: i32) -> String {
+    let first_variable = context.variables.len();
+    let assembly = stmt_assembly(stmt, context, address);
+    // the variables created inside a statement can't be used after it
+    match stmt {
+        Stmt::If { token, .. } | Stmt::While { token, .. } | Stmt::DoWhile { token, .. } | Stmt::For { token, .. } => {
+            context.hide(first_variable, &token.span.line.to_string());
+        }
+        Stmt::Function { name, .. } => context.hide(first_variable, &name.value),
+        _ => {}
+    }
+    assembly
+}
+/// returns the assembly of a statement, see `solve_stmt`
+fn stmt_assembly(stmt: &Stmt, context: &mut Context, address: i32) -> String {
+    match stmt {
+        Stmt::Decl { type_name, name, value } => {
+            let v_type = variable_type(type_name);
+
+            let values = match value {
+                Expr::Array { values, .. } => Some(values.clone()),
+                // a string is an array of its characters
+                Expr::Literal(token) if token.token_type == TokenType::DoubleQuote => Some(token.value.chars()
+                    .map(|x| Expr::Literal(Token { token_type: TokenType::SingleQuote, value: x.to_string(), span: token.span }))
+                    .collect()),
+                _ => None,
+            };
+            if let Some(values) = values {
+                // the elements are next to each other, so they are placed before the values are worked out
+                let first = reserve_array(context, v_type, values.len());
+                let mut assembly = String::new();
+                for (i, value) in values.iter().enumerate() {
+                    let value = solve_expr(value, context, "R0");
+                    let address = context.variables[first + i].address;
+                    assembly += format!("{}\nSTR R0 #{} ; store array element {}\n", value, address.to_string(), i).as_str();
                 }
+                name_array(context, first, &name.value);
+                return assembly;
+            }
 
-                let value = solve_node(node.operand2.as_ref().unwrap(), variables, "R0", virtual_registers, v_type, bytes);
-                let address = (crate::vc_8bit::MAXBYTE - (variables.len() as i32 + 1)).try_into().unwrap();
-                variables.push(Variable::new(var_name.token.value.clone(), address, v_type, false));
-                virtual_registers[0] = address;
-                format!("{}\nSTR R0 #{} ; store created variable", value, address.to_string())
-            } else {
-                // Non-type assignments
-                let var_name = node.operand1.as_ref().unwrap();
-                let var_type = variables[variables.iter().position(|x| x.name == var_name.token.value).unwrap()].var_type;
-                let value = solve_node(node.operand2.as_ref().unwrap(), variables, "R0", virtual_registers, var_type, bytes);
-                let address = variables[variables.iter().position(|x| x.name == var_name.token.value).unwrap()].address;
-                
-                virtual_registers[0] = address;
+            let value = solve_expr(value, context, "R0");
+            let address = next_address(&context.variables);
+            context.variables.push(Variable::new(name.value.clone(), address, v_type, false));
+            format!("{}\nSTR R0 #{} ; store created variable", value, address.to_string())
+        }
+        Stmt::Array { type_name, name, size } => {
+            // the elements hold what is in memory, which is 0 when the program starts
+            let length = Byte::from_string(constant_byte(size, "Array size")).to_i32() as usize;
+            let first = reserve_array(context, variable_type(type_name), length);
+            name_array(context, first, &name.value);
+            String::new()
+        }
+        Stmt::Assign { target, value } => match target {
+            Expr::Variable(name) => {
+                let address = find_variable(&context.variables, name).address;
+                let value = solve_expr(value, context, "R0");
                 format!("{}\nSTR R0 #{} ; store variable", value, address.to_string())
             }
+            Expr::Index { array, index } => {
+                let (base, length) = find_array(&context.variables, array);
+                let value = solve_expr(value, context, "R0");
+                if let Expr::Literal(token) = index.as_ref() {
+                    let address = Byte::from_string((base.to_i32() - token.value.parse::<i32>().unwrap()).to_string());
+                    return format!("{}\nSTR R0 #{} ; store array element {}", value, address.to_string(), token.value);
+                }
+                let index = solve_right(index, context, "R1", "R0");
+                format!("{value}\n{index} ; get index\n{}", solve_element(context, "STR", "R0", "R1", "R2", base, length))
+            }
+            _ => panic!("Can't assign a value to '{}' in line {}", fmt_expr(target), target.line()),
         }
-        TokenType::AndAnd => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
+        Stmt::If { condition, body, else_body, .. } => {
+            let check = solve_condition(condition, context);
+            // the body starts after the check and the 2 byte jump over it
+            let (lines, end) = solve_block(body, context, address + assembled_size(&check) + 2);
+            if else_body.is_empty() {
+                return format!("{check}\nJEQ {end} ; jump if false\n{lines}");
+            }
 
-            format!("{} ; get value left\n{} ; get value right\nAND {} R3 ; and value '&&'", value_left, value_right, register)
+            // the body ends by jumping over the else body
+            let else_start = end + 2;
+            let (else_lines, else_end) = solve_block(else_body, context, else_start);
+            format!("{check}\nJEQ {else_start} ; jump to else if false\n{lines}JMP {else_end} ; skip else\n{else_lines}")
         }
-        TokenType::OrOr => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
-    
-            format!("{} ; get value left\n{} ; get value right\nOR {} R3 ; or value '||'", value_left, value_right, register)
+        Stmt::While { condition, body, .. } => {
+            let check = solve_condition(condition, context);
+            let (lines, end) = solve_block(body, context, address + assembled_size(&check) + 2);
+            // the jump back to the start is part of the loop
+            let lines = resolve_loop_jumps(&lines, end + 2, address);
+            format!("{check}\nJEQ {} ; jump if false\n{lines}JMP {address} ; jump back to start", end + 2)
         }
-        TokenType::Increment => {
-            let value = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            format!("{} ; get value\nINC {} ; increment", value, register)
+        Stmt::DoWhile { body, condition, .. } => {
+            let (lines, condition_start) = solve_block(body, context, address);
+            let check = solve_condition(condition, context);
+            // after the check are the jump out of the loop and the jump back to the start
+            let end = condition_start + assembled_size(&check) + 4;
+            let lines = resolve_loop_jumps(&lines, end, condition_start);
+            format!("{lines}{check}\nJEQ {end} ; jump if false\nJMP {address} ; jump back to start")
         }
-        TokenType::Decrement => {
-            let value = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            format!("{} ; get value\nDEC {} ; increment", value, register)
-        }
-        TokenType::Not => {
-            let value = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
+        Stmt::For { init, condition, step, body, .. } => {
+            let init = match init {
+                Some(init) => format!("{}\n", solve_stmt(init, context, address).trim()),
+                None => String::new(),
+            };
+            let condition_start = address + assembled_size(&init);
+            let check = condition.as_ref().map(|x| solve_condition(x, context));
+            let body_start = condition_start + check.as_ref().map_or(0, |x| assembled_size(x) + 2);
+
+            let (lines, step_start) = solve_block(body, context, body_start);
+            let step = match step {
+                Some(step) => format!("{}\n", solve_stmt(step, context, step_start).trim()),
+                None => String::new(),
+            };
+            let end = step_start + assembled_size(&step) + 2;
+            let lines = resolve_loop_jumps(&lines, end, step_start);
 
-            format!("{} ; get value\nNOT {} ; not value", value, register)
+            // without a condition the loop only ends with break
+            let check = check.map_or(String::new(), |x| format!("{x}\nJEQ {end} ; jump if false\n"));
+            format!("{init}{check}{lines}{step}JMP {condition_start} ; jump back to the condition")
         }
-        TokenType::And => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
+        Stmt::Break(_) => BREAK.to_string(),
+        Stmt::Continue(_) => CONTINUE.to_string(),
+        Stmt::Expr(expr) => solve_expr(expr, context, "R0"),
+        Stmt::Function { return_type, name, parameters, body } => {
+            if BUILT_IN.contains(&name.value.as_str()) || context.functions.iter().any(|x| x.name == name.value) {
+                panic!("Function '{}' is already defined in line {}", name.value, name.span.line);
+            }
+            let return_type = match return_type.value.as_str() {
+                "void" => None,
+                _ => Some(variable_type(return_type)),
+            };
 
-            format!("{} ; get value left\n{} ; get value right\nAND {} R3 ; and value", value_left, value_right, register)
-        }
-        TokenType::Or => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
+            // the parameters and variables of the function have one place in memory, the same as any other variable
+            let mut variables: Vec<Variable> = vec![];
+            for (type_name, parameter) in parameters {
+                if type_name.value.ends_with("[]") {
+                    panic!("Parameter '{}' can't be an array in line {}", parameter.value, parameter.span.line);
+                }
+                let address = next_address(&context.variables);
+                context.variables.push(Variable::new(parameter.value.clone(), address, variable_type(type_name), false));
+                variables.push(context.variables.last().unwrap().clone());
+            }
+
+            // the code before the function jumps over it
+            let start = address + 2;
+            context.functions.push(Function { name: name.value.clone(), return_type, parameters: variables, address: start, return_address: 0 });
+            context.function = Some(context.functions.len() - 1);
+            let (lines, return_address) = solve_block(body, context, start);
+            context.function = None;
+            context.functions.last_mut().unwrap().return_address = return_address;
 
-            format!("{} ; get value left\n{} ; get value right\nOR {} R3 ; or value", value_left, value_right, register)
+            let lines = lines.replace(RETURN, &format!("JMP {return_address} ; return"));
+            format!("JMP {} ; skip function {}\n{lines}JMP 0 ; go back to the caller of {}", return_address + 2, name.value, name.value)
         }
-        TokenType::XOR => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
+        Stmt::Return { value: Some(value), .. } => format!("{}\n{RETURN}", solve_expr(value, context, "R0")),
+        Stmt::Return { value: None, .. } => RETURN.to_string(),
+    }
+}
+/// returns the type of the variables a type name creates
+fn variable_type(type_name: &Token) -> VariableType {
+    match type_name.value.as_str() {
+        "bool" | "bool[]" => VariableType::Bool,
+        "char" | "char[]" => VariableType::Char,
+        "uint8" | "uint8[]" => VariableType::UInt8,
+        "let" => VariableType::None,
+        _ => panic!("Invalid variable type '{}' in line {}", type_name.value, type_name.span.line)
+    }
+}
+
+/// `break` and `continue` jump to addresses that aren't known until the whole loop is made.
+/// They are written with these placeholders and the loop they are in replaces them, inner loops replace theirs first
+const BREAK: &str = "JMP 0 ; break";
+const CONTINUE: &str = "JMP 0 ; continue";
+/// `return` jumps to the end of the function it is in, which is replaced once the function is made
+const RETURN: &str = "JMP 0 ; return";
+fn resolve_loop_jumps(lines: &str, break_address: i32, continue_address: i32) -> String {
+    lines.replace(BREAK, &format!("JMP {break_address} ; break")).replace(CONTINUE, &format!("JMP {continue_address} ; continue"))
+}
+/// returns assembly that turns the zero flag on if the condition is false
+fn solve_condition(condition: &Expr, context: &mut Context) -> String {
+    let value = solve_expr(condition, context, "R3");
+    format!("{value} ; get value for statement\nCLR R2 ; set R2 to 0\nSUB R2 R3 ; check if statement is true")
+}
+/// returns the assembly of the statements and the address after them. Every statement starts where the last one ended
+fn solve_block(body: &[Stmt], context: &mut Context, address: i32) -> (String, i32) {
+    let mut end = address;
+    let mut lines = String::new();
+    for stmt in body {
+        let line = solve_stmt(stmt, context, end);
+        end += assembled_size(&line);
+        lines += format!("{}\n", line.trim()).as_str();
+    }
+    (lines, end)
+}
 
-            format!("{} ; get value left\n{} ; get value right\nXOR {} R3 ; xor value", value_left, value_right, register)
+/// # solve_expr
+/// Turns an expression into assembly that leaves its value in a register
+/// # Arguments
+/// * `expr` - The expression
+/// * `context` - The variables and functions created so far
+/// * `register` - The register the value is left in, `R0` to `R3`
+/// # Examples
+/// Casting to `bool` gives `11111111` for anything but 0, and casting between `uint8` and `char` keeps the byte
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "bool b = (bool)7;\nuint8 z = (uint8)b;\nuint8 zero = 0;\nuint8 f = (uint8)(bool)zero;
+/// char c = (char)104;\nuint8 n = (uint8)c - (uint8)'a';";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let value = |name: &str| computer.ram.read(variables.iter().find(|x| x.name == name).unwrap().address).to_i32();
+/// assert_eq!((value("b"), value("z"), value("f")), (255, 255, 0));
+/// assert_eq!((value("c"), value("n")), ('h' as i32, 7));
+/// ```
+/// `++` and `--` store the new value back in an array element, with a constant index or one worked out while running
+/// ```
+/// use vc_8bit::c_lang::{interpret_with_variables, parse, tokenize};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let code = "uint8 list[3];\nuint8 i = 1;\nlist[i]++;\nlist[i]++;\nlist[2]++;\nlist[2]++;\nlist[2]--;\nlist[i - 1]++;
+/// uint8 a = list[0];\nuint8 b = list[1];\nuint8 c = list[2];";
+/// let (assembly, variables) = interpret_with_variables(&parse(&tokenize(code)));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// let value = |name: &str| computer.ram.read(variables.iter().find(|x| x.name == name).unwrap().address).to_i32();
+/// assert_eq!((value("a"), value("b"), value("c"), value("i")), (1, 2, 1, 1));
+/// ```
+pub fn solve_expr(expr: &Expr, context: &mut Context, register: &str) -> String {
+    match expr {
+        Expr::Literal(token) => {
+            let value = match token.token_type {
+                TokenType::Boolean => if token.value == "true" { Byte::full() } else { Byte::zero() },
+                TokenType::SingleQuote => Byte::from_u8(token.value.chars().next().unwrap() as u8),
+                _ => Byte::from_string(token.value.clone()),
+            };
+            format!("MOV {} #{}", register, value.to_string())
+        }
+        Expr::Variable(token) => {
+            let var = find_variable(&context.variables, token);
+            format!("LDR {} #{} ; load variable", register, var.address.to_string())
+        }
+        Expr::Array { token, .. } => {
+            panic!("Array values can only be used to create an array in line {}", token.span.line);
+        }
+        Expr::Index { array, index } => {
+            let (base, length) = find_array(&context.variables, array);
+            if let Expr::Literal(token) = index.as_ref() {
+                let address = Byte::from_string((base.to_i32() - token.value.parse::<i32>().unwrap()).to_string());
+                return format!("LDR {} #{} ; load array element {}", register, address.to_string(), token.value);
+            }
+            let value = solve_expr(index, context, register);
+            let other = if register == "R0" { "R1" } else { "R0" };
+            format!("{value} ; get index\n{}", solve_element(context, "LDR", register, register, other, base, length))
+        }
+        Expr::Unary { op, operand } => match op.token_type {
+            TokenType::Increment | TokenType::Decrement => {
+                let (instruction, comment) = match op.token_type {
+                    TokenType::Increment => ("INC", "increment"),
+                    _ => ("DEC", "decrement"),
+                };
+                if let Expr::Index { array, index } = operand.as_ref() {
+                    if !matches!(index.as_ref(), Expr::Literal(_)) {
+                        // the index is worked out once and stays in its register, so the element loaded is the one stored back
+                        let (base, length) = find_array(&context.variables, array);
+                        let mut free = ["R0", "R1", "R2", "R3"].into_iter().filter(|x| *x != register);
+                        let (index_register, other) = (free.next().unwrap(), free.next().unwrap());
+                        let value = solve_expr(index, context, index_register);
+                        let load = solve_element(context, "LDR", register, index_register, other, base, length);
+                        let store = solve_element(context, "STR", register, index_register, other, base, length);
+                        return format!("{value} ; get index\n{load}\n{instruction} {register} ; {comment}\n{store}");
+                    }
+                }
+                let value = solve_expr(operand, context, register);
+                let mut code = format!("{} ; get value\n{} {} ; {}", value, instruction, register, comment);
+                // the new value is stored back in the variable or array element
+                match operand.as_ref() {
+                    Expr::Variable(name) => {
+                        code += format!("\nSTR {} #{} ; store variable", register, find_variable(&context.variables, name).address.to_string()).as_str();
+                    }
+                    Expr::Index { array, index } => if let Expr::Literal(token) = index.as_ref() {
+                        let (base, _) = find_array(&context.variables, array);
+                        let address = Byte::from_string((base.to_i32() - token.value.parse::<i32>().unwrap()).to_string());
+                        code += format!("\nSTR {} #{} ; store array element {}", register, address.to_string(), token.value).as_str();
+                    }
+                    _ => {}
+                }
+                code
+            }
+            TokenType::Not => {
+                let value = solve_expr(operand, context, register);
+                format!("{} ; get value\nNOT {} ; not value", value, register)
+            }
+            _ => {
+                // -x is 0 - x
+                let zero = Expr::Literal(Token { token_type: TokenType::Number, value: "0".to_string(), span: op.span });
+                let negative = Expr::Binary { op: op.clone(), left: Box::new(zero), right: operand.clone() };
+                solve_expr(&negative, context, register)
+            }
+        }
+        Expr::Binary { op, left, right } => solve_binary(op, left, right, context, register),
+        Expr::Cast { type_name, operand } => {
+            let value = solve_expr(operand, context, register);
+            // char and uint8 have the same bits
+            if type_name.value != "bool" {
+                return value;
+            }
+            // a bool is 0 if the value is 0 and 255 if it isn't
+            let other = if register == "R0" { "R1" } else { "R0" };
+            format!("{value} ; get value\nCLR {other}\nADD {register} {other} ; set the flags from the value\nCMP_ZRO {register} ; 255 if the value is 0\nNOT {register} ; bool value")
+        }
+        Expr::Call { name, arguments } => solve_call(name, arguments, context, register),
+    }
+}
+fn solve_binary(op: &Token, left: &Expr, right: &Expr, context: &mut Context, register: &str) -> String {
+    match op.token_type {
+        TokenType::AndAnd | TokenType::OrOr | TokenType::And | TokenType::Or | TokenType::XOR => {
+            // conditions are worked out in R3, so their right side uses R2
+            let other = if register == "R3" { "R2" } else { "R3" };
+            let value_left = solve_expr(left, context, register);
+            let value_right = solve_right(right, context, other, register);
+            let logic = match op.token_type {
+                TokenType::AndAnd => "AND {} {} ; and value '&&'",
+                TokenType::OrOr => "OR {} {} ; or value '||'",
+                TokenType::And => "AND {} {} ; and value",
+                TokenType::Or => "OR {} {} ; or value",
+                _ => "XOR {} {} ; xor value",
+            }.replacen("{}", register, 1).replacen("{}", other, 1);
+
+            format!("{} ; get value left\n{} ; get value right\n{}", value_left, value_right, logic)
         }
         TokenType::ShiftLeft | TokenType::ShiftRight => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, register, virtual_registers, expected_value, bytes);
-            let op2 = node.operand2.clone().unwrap().token;
-            let binary: String = if op2.token_type == TokenType::Number {
-                Byte::from_string(op2.value.clone()).to_string()
-            } else {
-                panic!("Shifting requires constant value in line {}", node.line);
-            };
+            let value_left = solve_expr(left, context, register);
+            let binary = constant_byte(right, "Shifting");
 
-            if node.token.token_type == TokenType::ShiftLeft {
+            if op.token_type == TokenType::ShiftLeft {
                 format!("{} ; get value left\nSHL {} #{}", value_left, register, binary)
             }
             else {
                 format!("{} ; get value left\nSHR {} #{}", value_left, register, binary)
             }
         }
-        TokenType::EqualCompare | TokenType::NotEqual | TokenType::GreaterThan | TokenType::GreaterThanOrEqualTo | TokenType::LessThan |TokenType::LessThanOrEqualTo => {
-            let virtual_registers_clone: [Byte; 4] = *virtual_registers;
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, "R0", virtual_registers, VariableType::None, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R1", virtual_registers, VariableType::None, bytes);
-            let mut value = String::new();
-            let virtual_registers_clone_2: [Byte; 4] = *virtual_registers;
-            *virtual_registers = virtual_registers_clone;
-            
-            let left_byte = value_left.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-            value += format!("{} ; value left\n", value_left).as_str();
-            virtual_registers[0] = Byte::from_string(left_byte.clone());
-            
-            let right_byte = value_right.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-            value += format!("{} ; value right\n", value_right).as_str();
-            virtual_registers[1] = Byte::from_string(right_byte.clone());
+        TokenType::EqualCompare | TokenType::NotEqual | TokenType::GreaterThan | TokenType::GreaterThanOrEqualTo | TokenType::LessThan | TokenType::LessThanOrEqualTo => {
+            let value_left = solve_expr(left, context, "R0");
+            let value_right = solve_right(right, context, "R1", "R0");
+            let value = format!("{} ; value left\n{} ; value right\n", value_left, value_right);
 
-            let logic = match node.token.token_type {
+            let logic = match op.token_type {
                 TokenType::EqualCompare => "SUB R0 R1\nCMP_ZRO R0",
                 TokenType::NotEqual => "SUB R0 R1\nCMP_ZRO R0\nNOT R0",
                 TokenType::GreaterThan => "SUB R0 R1\nCMP_ABV R0",
                 TokenType::GreaterThanOrEqualTo => "SUB R0 R1\nCMP_ABV R0\nCMP_ZRO R1\nOR R0 R1",
                 TokenType::LessThan => "SUB R0 R1\nCMP_NEG R0",
-                TokenType::LessThanOrEqualTo => "SUB R0 R1\nCMP_NEG R0\nCMP_ZRO R1\nOR R0 R1",
-                _ => panic!("Invalid logic operator")
+                _ => "SUB R0 R1\nCMP_NEG R0\nCMP_ZRO R1\nOR R0 R1",
             };
 
-            *virtual_registers = virtual_registers_clone_2;
-
             if register == "R0" {
                 format!("{}{} ; compare", value, logic)
             }
@@ -1197,51 +1532,16 @@ pub fn solve_node(node: &ExprNode, variables: &mut Vec<Variable>, register: &str
                 format!("{}{} ; compare\nCPY {} R0 ; copy value right", value, logic, register)
             }
         }
-        TokenType::Boolean => {
-            if expected_value == VariableType::Bool || expected_value == VariableType::None {
-                virtual_registers[get_index_from_register(register)] = if node.token.value == "true" { Byte::full() } else { Byte::zero() };
-                format!("MOV {} #{}", register, virtual_registers[get_index_from_register(register)].to_string())
-            } else {
-                panic!("Invalid type for boolean")
-            }
-        }
-        TokenType::SingleQuote => {
-            if expected_value == VariableType::Char || expected_value == VariableType::None {
-                let c = node.token.value.clone().chars().next().unwrap();
-                let ascii_value = c as u8;
-                virtual_registers[get_index_from_register(register)] = Byte::from_u8(ascii_value);
-                format!("MOV {} #{}", register, Byte::from_u8(ascii_value).to_string())
-            } else {
-                panic!("Invalid type for number")
-            }
-        }
-        TokenType::Number => {
-            if expected_value == VariableType::UInt8 || expected_value == VariableType::None {
-                virtual_registers[get_index_from_register(register)] = Byte::from_string(node.token.value.clone());
-                format!("MOV {} #{}", register, virtual_registers[get_index_from_register(register)].to_string())
-            } else {
-                panic!("Invalid type for number")
-            }
-        } 
-        TokenType::Plus | TokenType::Dash | TokenType::Star | TokenType::Slash => {
-            let value_left = solve_node(node.operand1.as_ref().unwrap(), variables, "R0", virtual_registers, VariableType::UInt8, bytes);
-            let value_right = solve_node(node.operand2.as_ref().unwrap(), variables, "R1", virtual_registers, VariableType::UInt8, bytes);
-            let mut value = String::new();
-            
-            let left_byte = value_left.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-            value += format!("{} ; value left\n", value_left).as_str();
-            virtual_registers[0] = Byte::from_string(left_byte.clone());
-            
-            let right_byte = value_right.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-            value += format!("{} ; value right\n", value_right).as_str();
-            virtual_registers[1] = Byte::from_string(right_byte.clone());
+        _ => {
+            let value_left = solve_expr(left, context, "R0");
+            let value_right = solve_right(right, context, "R1", "R0");
+            let value = format!("{} ; value left\n{} ; value right\n", value_left, value_right);
 
-            let math = match node.token.token_type {
+            let math = match op.token_type {
                 TokenType::Plus => "ADD",
                 TokenType::Dash => "SUB",
                 TokenType::Star => "MUL",
-                TokenType::Slash => "DIV",
-                _ => panic!("Invalid math operator")
+                _ => "DIV",
             };
 
             if register == "R0" {
@@ -1251,134 +1551,204 @@ pub fn solve_node(node: &ExprNode, variables: &mut Vec<Variable>, register: &str
                 format!("{}{} R0 R1 ; math\nCPY {} R0 ; copy value right", value, math, register)
             }
         }
-        TokenType::Identifier => {
-            if !node.is_func() {
-                for var in variables {
-                    if &var.name == &node.token.value {
-                        if expected_value != var.var_type && expected_value != VariableType::None {
-                            panic!("Invalid type for variable '{}'", var.name);
-                        }
-                        virtual_registers[get_index_from_register(register)] = var.address;
-                        return format!("LDR {} #{} ; load variable", register, var.address.to_string());
-                    }
-                }
-                panic!("Variable '{}' does not exist in line {}", &node.token.value, node.line);
-            }
-            let func_name = node.func_name.clone().unwrap();
-            if func_name == "print" {
-                let v = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value = solve_node(&v.clone(), variables, "R2", virtual_registers, VariableType::Char, bytes);
-                let byte_value = value.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-                
-                virtual_registers[2] = Byte::from_string(byte_value);
-                format!("{}\nMSG R2 ; print value", value)
-            }
-            else if func_name == "out" {
-                let v = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value = solve_node(&v.clone(), variables, "R2", virtual_registers, VariableType::None, bytes);
-
-                format!("{}\nOUT R2 ; print value as byte", value)
-            }
-            else if func_name == "goto" {
-                let v = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value: String = if v.token.token_type == TokenType::Number {
-                    Byte::from_string(v.token.value.clone()).to_string()
-                } else {
-                    panic!("jumping requires constant value in line {}", node.line);
-                };
-
-                format!("JMP #{value}")
-            }
-            else if func_name == "to_char" {                
-                let v = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value = solve_node(&v.clone(), variables, "R3", virtual_registers, VariableType::UInt8, bytes);
+    }
+}
+/// works out the right side of a binary operator while the left side is in the register `left`.
+/// Anything more than a value or variable can use every register, so the left side is held in memory while it runs
+fn solve_right(right: &Expr, context: &mut Context, register: &str, left: &str) -> String {
+    if matches!(right, Expr::Literal(_) | Expr::Variable(_)) {
+        return solve_expr(right, context, register);
+    }
+    let address = context.hold().to_string();
+    let value = solve_expr(right, context, register);
+    context.release();
+    format!("STR {left} #{address} ; hold value left\n{value}\nLDR {left} #{address} ; get value left back")
+}
 
-                let before = format!("{}\n", value);
+/// The functions every program has
+const BUILT_IN: [&str; 7] = ["print", "print_str", "out", "goto", "to_char", "write_port", "read_port"];
+/// returns if the expression calls a function defined in the code
+fn has_call(expr: &Expr, context: &Context) -> bool {
+    match expr {
+        Expr::Call { name, arguments } => context.functions.iter().any(|x| x.name == name.value) || arguments.iter().any(|x| has_call(x, context)),
+        Expr::Array { values, .. } => values.iter().any(|x| has_call(x, context)),
+        Expr::Index { index, .. } => has_call(index, context),
+        Expr::Binary { left, right, .. } => has_call(left, context) || has_call(right, context),
+        Expr::Unary { operand, .. } | Expr::Cast { operand, .. } => has_call(operand, context),
+        Expr::Literal(_) | Expr::Variable(_) => false,
+    }
+}
+/// # solve_function_call
+/// Calls a function defined in the code. There is no stack, the VC can't read memory at an address that is worked out while running.
+/// * The arguments are stored in the variables of the parameters
+/// * The caller stores the address to come back to in the `JMP` at the end of the function, then jumps to the start of the function
+/// * The value is returned in `R0`, and the function can change every register
+fn solve_function_call(index: usize, name: &Token, arguments: &[Expr], context: &mut Context, register: &str) -> String {
+    if context.function == Some(index) {
+        panic!("Function '{}' can't call itself in line {}, there is no stack for recursion", name.value, name.span.line);
+    }
+    let function = context.functions[index].clone();
+    if arguments.len() != function.parameters.len() {
+        panic!("Function '{}' takes {} argument(s) but {} were given in line {}", name.value, function.parameters.len(), arguments.len(), name.span.line);
+    }
 
-                if register == "R3" {
-                    format!("{}MOV R2 48 ; 48 is ascii '0'\nADD R3 R2 ; get ascii", before)
-                }
-                else {
-                    format!("{}MOV R2 48 ; 48 is ascii '0'\nADD R3 R2 ; get ascii\nCPY {} R3 ; move value to correct register", before, register)
-                }
-            }
-            else if func_name == "write_port" {                
-                let v2 = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value2: String = if v2.token.token_type == TokenType::Number {
-                    Byte::from_string(v2.token.value.clone()).to_string()
-                } else {
-                    panic!("Shifting requires constant value in line {}", node.line);
-                };
-                let v1 = node.func_parameters.clone().unwrap()[1][0].clone().unwrap();
-                let value1 = solve_node(&v1.clone(), variables, "R2", virtual_registers, VariableType::None, bytes);
+    let mut assembly = String::new();
+    if arguments.iter().skip(1).any(|x| has_call(x, context)) {
+        // a call in a later argument could change the parameters already stored, so every argument is held until all are worked out
+        let mut held: Vec<String> = vec![];
+        for argument in arguments {
+            let value = solve_expr(argument, context, "R0");
+            let address = context.hold().to_string();
+            assembly += format!("{value}\nSTR R0 #{address} ; hold argument\n").as_str();
+            held.push(address);
+        }
+        for (address, parameter) in held.iter().zip(&function.parameters) {
+            assembly += format!("LDR R0 #{address}\nSTR R0 #{} ; store argument\n", parameter.address.to_string()).as_str();
+            context.release();
+        }
+    }
+    else {
+        for (argument, parameter) in arguments.iter().zip(&function.parameters) {
+            let value = solve_expr(argument, context, "R0");
+            assembly += format!("{value}\nSTR R0 #{} ; store argument\n", parameter.address.to_string()).as_str();
+        }
+    }
 
-                format!("{value1}\nWPRT R2 #{value2}")
-            }
-            else if func_name == "read_port" {                
-                let v1 = node.func_parameters.clone().unwrap()[0][0].clone().unwrap();
-                let value1: String = if v1.token.token_type == TokenType::Number {
-                    Byte::from_string(v1.token.value.clone()).to_string()
-                } else {
-                    panic!("Shifting requires constant value in line {}", node.line);
-                };
+    // the address to come back to isn't known until the code is placed, so the assembler fills it in from a label
+    let label = context.label("call");
+    assembly += format!("MOV R0 {label} ; address to come back to\nSTR R0 {} ; set the end of '{}' to jump back\nJMP {} ; call {}\n{label}:", function.return_address + 1, name.value, function.address, name.value).as_str();
+    if register != "R0" {
+        assembly += format!("\nCPY {register} R0 ; copy value returned").as_str();
+    }
+    assembly
+}
+fn solve_call(name: &Token, arguments: &[Expr], context: &mut Context, register: &str) -> String {
+    if let Some(index) = context.functions.iter().position(|x| x.name == name.value) {
+        return solve_function_call(index, name, arguments, context, register);
+    }
+    let count = match name.value.as_str() {
+        "print" | "print_str" | "out" | "goto" | "to_char" | "read_port" => 1,
+        "write_port" => 2,
+        _ => panic!("Function '{}' not defined in line {}", name.value, name.span.line),
+    };
+    if arguments.len() != count {
+        panic!("Function '{}' takes {} argument(s) but {} were given in line {}", name.value, count, arguments.len(), name.span.line);
+    }
 
-                format!("RPRT {register} #{value1}")
-            }
-            else {
-                panic!("Function '{}' not defined in line {}", func_name, node.line);
-            }
+    match name.value.as_str() {
+        "print" => {
+            let value = solve_expr(&arguments[0], context, "R2");
+            format!("{}\nMSG R2 ; print value", value)
         }
-        TokenType::OpenBracket => {
-            let value = solve_node(node.bracket_set.as_ref().unwrap().iter().nth(0).unwrap().as_ref().unwrap(), variables, "R3", virtual_registers, VariableType::None, bytes);
-            let name = node.operand1.as_ref().unwrap().token.value.clone();
-            let func = |v_name: String, name: &str| -> bool {
-                let v = v_name.split("|").collect::<Vec<&str>>()[0];
-                v == name
-            };
-
-            if !variables.iter().any(|v| func(v.name.clone(), &name)) {
-                panic!("Variable '{}' does not exist in line {}", name, node.line);
-            }
-            
-            let variable = variables.iter().find(|v| func(v.name.clone(), &name)).unwrap();
-            
-            if !variable.is_array {
-                panic!("Variable '{}' is not an array in line {}", name, node.line);
-            }
-            
-            let byte = value.split(" ").collect::<Vec<&str>>()[2].to_string().chars().skip(1).collect::<String>();
-            let index = Byte::from_string(byte.clone()).reverse().to_u8();
-            let address = variables.iter().find(|v| v.name == format!("{}|{}", name, index)).unwrap().address.to_string();
+        "print_str" => solve_print_str(&arguments[0], context),
+        "out" => {
+            let value = solve_expr(&arguments[0], context, "R2");
+            format!("{}\nOUT R2 ; print value as byte", value)
+        }
+        "goto" => {
+            let value = constant_byte(&arguments[0], "jumping");
+            format!("JMP #{value}")
+        }
+        "to_char" => {
+            let value = solve_expr(&arguments[0], context, "R3");
+            let before = format!("{}\n", value);
 
-            format!("{value} ; get value for array\nLDR {register} #{address}")
-        }
-        TokenType::Statement => {
-            let value = solve_node(node.statement_insides.as_ref().unwrap().iter().nth(0).unwrap().as_ref().unwrap(), variables, "R3", virtual_registers, expected_value, bytes);
-            let check = format!("{value} ; get value for statement\nMOV R2 0 ; set R2 to 0\nSUB R2 R3 ; check if statement is true");
-            let mut lines = String::new();
-            for i in 0..node.statement_lines.as_ref().unwrap().len() {
-                lines += format!("{}\n", solve_node(node.statement_lines.as_ref().unwrap().iter().nth(i).unwrap(), variables, register, virtual_registers, expected_value, bytes)).as_str();
-            }
-            let assembly_to_binary = &compile_assembly_to_binary(format!("{}\n{}", check.clone(), lines).as_str());
-            let bytes_vec = string_to_bytes(assembly_to_binary);
-            let end_bytes = bytes_vec.len() as i32 + *bytes + 2;
-            
-            if node.token.value == "if" {
-                let jump = format!("{check}\nJMP_ZRO {} ; jump if false", end_bytes);
-                format!("{jump}\n{lines}")
-            }
-            else if node.token.value == "while" {
-                let jump = format!("{check}\nJMP_ZRO {} ; jump if false", end_bytes + 1);
-                format!("{jump}\n{lines}\nJMP {bytes} ; jump back to start")
+            if register == "R3" {
+                format!("{}MOV R2 48 ; 48 is ascii '0'\nADD R3 R2 ; get ascii", before)
             }
             else {
-                panic!("Unsupported statement: {:?} in line {}", node.token.token_type, node.line);
+                format!("{}MOV R2 48 ; 48 is ascii '0'\nADD R3 R2 ; get ascii\nCPY {} R3 ; move value to correct register", before, register)
             }
         }
+        "write_port" => {
+            let port = constant_byte(&arguments[0], "Port address");
+            let value = solve_expr(&arguments[1], context, "R2");
+            format!("{value}\nWPRT R2 #{port}")
+        }
         _ => {
-            panic!("Unsupported operation: {:?} in line {}", node.token.token_type, node.line);
+            let port = constant_byte(&arguments[0], "Port address");
+            format!("RPRT {register} #{port}")
+        }
+    }
+}
+
+/// # solve_print_str
+/// Prints every character of a string or a `char` array with `MSG`. Either every character gets its own `MSG`,
+/// or a loop loads the characters one after another, whichever takes fewer bytes. The characters of a string
+/// are put in the code for the loop to read
+/// # Examples
+/// A short string gets a `MSG` for every character
+/// ```
+/// use vc_8bit::c_lang::compile;
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let assembly = compile(&"print_str(\"Hi\");".to_string());
+/// assert_eq!(assembly.matches("MSG R0").count(), 2);
+/// assert!(!assembly.contains("%BYTE"));
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// assert_eq!(computer.cpu.reg_1.value.to_i32(), 'i' as i32); // the last character printed is left in R0
+/// ```
+/// A longer string is put in the code and printed with a loop
+/// ```
+/// use vc_8bit::c_lang::compile;
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let assembly = compile(&"print_str(\"Hello, world!\");".to_string());
+/// assert!(assembly.contains("%BYTE 72 101 108 108 111 44 32 119 111 114 108 100 33"));
+/// assert_eq!(assembly.matches("MSG R0").count(), 1);
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// assert_eq!(computer.cpu.reg_1.value.to_i32(), '!' as i32);
+/// assert_eq!(computer.cpu.reg_3.value.to_i32(), 0); // no characters left in R2
+/// ```
+/// A `char` array is printed from where its elements are, so changes to it are printed
+/// ```
+/// use vc_8bit::c_lang::compile;
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// use vc_8bit::vc_8bit::Computer;
+/// let assembly = compile(&"char[] short = \"ab\";\nprint_str(short);\nchar[] long = \"abcdefg\";\nlong[6] = 'z';\nprint_str(long);".to_string());
+/// assert_eq!(assembly.matches("MSG R0").count(), 3);
+/// let mut computer = Computer::new();
+/// computer.ram.insert_bytes(string_to_bytes(&compile_assembly_to_binary(&assembly)));
+/// computer.run();
+/// assert_eq!(computer.cpu.reg_1.value.to_i32(), 'z' as i32);
+/// ```
+fn solve_print_str(text: &Expr, context: &mut Context) -> String {
+    let (unrolled, data, first, length, step) = match text {
+        Expr::Literal(token) if token.token_type == TokenType::DoubleQuote => {
+            let bytes: Vec<u8> = token.value.chars().map(|x| x as u8).collect();
+            let unrolled = bytes.iter().map(|x| format!("MOV R0 #{}\nMSG R0 ; print character", Byte::from_u8(*x).to_string())).collect::<Vec<String>>();
+            let label = context.label("text");
+            let values = bytes.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ");
+            let data = format!("JMP {label}_end ; skip the text\n{label}: %BYTE {values}\n{label}_end:\n");
+            (unrolled, data, label, bytes.len(), "ADD")
+        }
+        Expr::Variable(token) => {
+            // the elements of an array go down from the first one
+            let (base, length) = find_array(&context.variables, token);
+            let unrolled = (0..length).map(|i| {
+                let address = Byte::from_string((base.to_i32() - i as i32).to_string());
+                format!("LDR R0 #{} ; load array element {}\nMSG R0 ; print character", address.to_string(), i)
+            }).collect::<Vec<String>>();
+            (unrolled, String::new(), format!("#{}", base.to_string()), length, "SUB")
         }
+        _ => panic!("'print_str' needs a string or a char array in line {}", text.line()),
+    };
+    let unrolled = unrolled.join("\n");
+    if length == 0 {
+        return unrolled;
     }
+
+    let label = context.label("print");
+    let opcode = isa::lookup("LDR").unwrap().encode(&[0, 0])[0];
+    let mut looped = format!("{data}MOV R1 {first} ; address of the first character\nMOV R2 #{} ; characters left\nMOV R3 #00000001\n", Byte::from_u8(length as u8).to_string());
+    looped += format!("{label}:\nSTR R1 {label}_character ; set the address of the instruction below\n%BYTE {opcode} ; LDR R0 with the address of the character\n{label}_character: %BYTE 0\n").as_str();
+    looped += format!("MSG R0 ; print character\n{step} R1 R3 ; address of the next character\nSUB R2 R3 ; one less character left\nJMP_ABV {label}").as_str();
+
+    if assembled_size(&looped) < assembled_size(&unrolled) { looped } else { unrolled }
 }
 
 pub fn get_index_from_register(register: &str) -> usize {
@@ -1400,12 +1770,11 @@ pub fn get_index_from_register(register: &str) -> usize {
 /// This function will panic if the code is invalid
 pub fn run_compiled_code_with_debugging(contents: &String) {
     println!("- CLANG:");
-    let lex = get_lexer_lines(contents);
-    let par = parse(lex);
-    for l in par.clone() { println!("{}", fmt_expr(&l.unwrap())) }
+    let program = parse(&tokenize(contents));
+    for stmt in &program { println!("{}", fmt_stmt(stmt)) }
     
     println!("\n- ASM:"); 
-    let value = interpret(par.clone());
+    let value = interpret(&program);
     println!("{value}");
 
     println!("\n- OUT:");
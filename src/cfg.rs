@@ -0,0 +1,383 @@
+use crate::assembly::{self, AssemblyOptions, SourceLine};
+use crate::isa::{self, Access, OperandKind};
+use crate::vc_8bit::Byte;
+use crate::vcimg::SegmentKind;
+
+/// # DecodedInstruction
+/// An instruction read back from the bytes of a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub address: i32,
+    pub mnemonic: &'static str,
+    /// 1 or 2 bytes
+    pub size: i32,
+    /// The registers the instruction uses in the order they are written, 0 for R0
+    pub registers: Vec<i32>,
+    /// The value, address, shift or port after the registers
+    pub value: Option<i32>,
+}
+impl DecodedInstruction {
+    /// returns the address the instruction jumps to if it is a jump
+    pub fn jump_target(&self) -> Option<i32> {
+        if self.mnemonic.starts_with("JMP") { self.value } else { None }
+    }
+    /// returns if the instruction only jumps when an ALU flag is set
+    pub fn is_conditional(&self) -> bool {
+        matches!(self.mnemonic, "JMP_NEG" | "JMP_ZRO" | "JMP_ABV")
+    }
+    /// returns the registers the instruction reads
+    pub fn reads(&self) -> Vec<i32> {
+        self.registers_where(|x| x.reads())
+    }
+    /// returns the registers the instruction writes
+    pub fn writes(&self) -> Vec<i32> {
+        self.registers_where(|x| x.writes())
+    }
+    /// returns the registers whose access in `isa::INSTRUCTIONS` matches
+    fn registers_where(&self, matches: impl Fn(Access) -> bool) -> Vec<i32> {
+        let operands = isa::lookup(self.mnemonic).map_or(&[][..], |x| x.operands);
+        operands.iter().filter(|x| x.kind == OperandKind::Register).zip(&self.registers)
+            .filter(|(field, _)| matches(field.access))
+            .map(|(_, register)| *register)
+            .collect()
+    }
+}
+impl std::fmt::Display for DecodedInstruction {
+    /// Writes the instruction the way the assembler reads it, like `MOV R0 5`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![self.mnemonic.to_string()];
+        parts.extend(self.registers.iter().map(|x| format!("R{x}")));
+        parts.extend(self.value.map(|x| x.to_string()));
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// # decode
+/// Reads the instruction at an address. A 2 byte instruction at the end of the bytes reads 0 as its second byte, like the RAM would.
+/// # Arguments
+/// * `bytes` - The program, starting at address 0
+/// * `address` - The address of the instruction
+/// # Returns
+/// * `Option<DecodedInstruction>` - `None` if the address is past the end or the byte is not an instruction
+/// # Examples
+/// ```
+/// use vc_8bit::cfg::decode;
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// let bytes = string_to_bytes(&compile_assembly_to_binary("MOV R1 5\nRPRT R2 3"));
+/// assert_eq!(decode(&bytes, 0).unwrap().to_string(), "MOV R1 5");
+/// assert_eq!(decode(&bytes, 2).unwrap().to_string(), "RPRT R2 3");
+/// ```
+pub fn decode(bytes: &[Byte], address: i32) -> Option<DecodedInstruction> {
+    let start = usize::try_from(address).ok()?;
+    let stream = bytes.get(start..)?.iter().take(2).map(|x| x.to_i32() as u8).collect::<Vec<u8>>();
+    let (definition, operands) = isa::decode(&stream)?;
+    let mut registers: Vec<i32> = vec![];
+    let mut value = None;
+    for (field, operand) in definition.operands.iter().zip(operands) {
+        match field.kind {
+            OperandKind::Register => registers.push(operand),
+            _ => value = Some(operand),
+        }
+    }
+    Some(DecodedInstruction { address, mnemonic: definition.mnemonic, size: definition.size as i32, registers, value })
+}
+
+/// How control gets from one block to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// The next instruction after the block
+    FallThrough,
+    /// A `JMP`
+    Jump,
+    /// A jump taken when a flag is set, holding the mnemonic that tests it like `JMP_ZRO`
+    Conditional(&'static str),
+}
+
+/// An edge between the blocks starting at `from` and `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: i32,
+    pub to: i32,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that always runs from the first to the last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: i32,
+    pub instructions: Vec<DecodedInstruction>,
+}
+impl BasicBlock {
+    /// returns the address after the last byte of the block
+    pub fn end(&self) -> i32 {
+        self.instructions.last().map_or(self.start, |x| x.address + x.size)
+    }
+}
+
+/// # ControlFlowGraph
+/// The blocks of a program that can run when it starts at the entry point, and the edges between them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub entry: i32,
+    /// The blocks in order of address
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+    /// Every instruction that can run, in order of address
+    pub instructions: Vec<DecodedInstruction>,
+    /// The addresses of instructions that go on to an address past the end of the code, along with that address
+    pub exits: Vec<(i32, i32)>,
+    /// Addresses in the program that are reached but don't hold an instruction
+    pub invalid: Vec<i32>,
+    /// The line of source each address came from, empty when the graph was built from bytes
+    pub sources: Vec<Option<SourceLine>>,
+}
+impl ControlFlowGraph {
+    /// # new
+    /// Builds the graph of a program by following every path from the entry point
+    /// # Arguments
+    /// * `bytes` - The program, starting at address 0
+    /// * `entry` - The address the program starts running at
+    /// # Examples
+    /// ```
+    /// use vc_8bit::cfg::{ControlFlowGraph, EdgeKind};
+    /// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+    /// let bytes = string_to_bytes(&compile_assembly_to_binary("loop: DEC R0\nJMP_ZRO end\nJMP loop\nend: HALT"));
+    /// let graph = ControlFlowGraph::new(&bytes, 0);
+    /// assert_eq!(graph.blocks.iter().map(|x| x.start).collect::<Vec<i32>>(), vec![0, 3, 5]);
+    /// assert!(graph.edges.iter().any(|x| x.from == 0 && x.to == 5 && x.kind == EdgeKind::Conditional("JMP_ZRO")));
+    /// ```
+    pub fn new(bytes: &[Byte], entry: i32) -> ControlFlowGraph {
+        ControlFlowGraph::with_code(bytes, entry, &vec![true; bytes.len()])
+    }
+    /// # with_code
+    /// Builds the graph like `new`, but running into an address that is not code counts as leaving the program
+    /// # Arguments
+    /// * `bytes` - The program, starting at address 0
+    /// * `entry` - The address the program starts running at
+    /// * `is_code` - If each address holds code
+    pub fn with_code(bytes: &[Byte], entry: i32, is_code: &[bool]) -> ControlFlowGraph {
+        let in_code = |address: i32| address >= 0 && is_code.get(address as usize).copied().unwrap_or(false);
+        let mut found: Vec<DecodedInstruction> = vec![];
+        let mut leaders: Vec<i32> = vec![entry];
+        let mut exits: Vec<(i32, i32)> = vec![];
+        let mut invalid: Vec<i32> = vec![];
+
+        // follow every path to find the instructions that can run
+        let mut work: Vec<i32> = vec![entry];
+        let mut seen: Vec<i32> = vec![];
+        while let Some(address) = work.pop() {
+            if seen.contains(&address) {
+                continue;
+            }
+            seen.push(address);
+            let instruction = match decode(bytes, address) {
+                Some(instruction) => instruction,
+                None => {
+                    if (address as usize) < bytes.len() {
+                        invalid.push(address);
+                    }
+                    continue;
+                }
+            };
+            let next = address + instruction.size;
+            let mut successors: Vec<i32> = vec![];
+            if let Some(target) = instruction.jump_target() {
+                successors.push(target);
+                leaders.push(target);
+                leaders.push(next);
+            }
+            if instruction.mnemonic != "HALT" && instruction.mnemonic != "JMP" {
+                successors.push(next);
+            }
+            for successor in successors {
+                if in_code(successor) {
+                    work.push(successor);
+                }
+                else {
+                    exits.push((address, successor));
+                }
+            }
+            found.push(instruction);
+        }
+        found.sort_by_key(|x| x.address);
+        leaders.sort();
+        leaders.dedup();
+        leaders.retain(|x| found.iter().any(|y| y.address == *x));
+
+        // split the instructions into blocks at every leader
+        let mut blocks: Vec<BasicBlock> = vec![];
+        let mut edges: Vec<Edge> = vec![];
+        for leader in &leaders {
+            let mut block = BasicBlock { start: *leader, instructions: vec![] };
+            let mut address = *leader;
+            while let Some(instruction) = found.iter().find(|x| x.address == address) {
+                block.instructions.push(instruction.clone());
+                address += instruction.size;
+                if instruction.jump_target().is_some() || instruction.mnemonic == "HALT" || leaders.contains(&address) {
+                    break;
+                }
+            }
+            let last = block.instructions.last().unwrap();
+            let next = last.address + last.size;
+            let found_at = |address: i32| found.iter().any(|x| x.address == address);
+            match last.jump_target() {
+                Some(target) if found_at(target) => edges.push(Edge { from: *leader, to: target, kind: if last.is_conditional() { EdgeKind::Conditional(last.mnemonic) } else { EdgeKind::Jump } }),
+                _ => {}
+            }
+            if last.mnemonic != "HALT" && last.mnemonic != "JMP" && found_at(next) {
+                edges.push(Edge { from: *leader, to: next, kind: EdgeKind::FallThrough });
+            }
+            blocks.push(block);
+        }
+        invalid.sort();
+        exits.sort();
+        ControlFlowGraph { entry, blocks, edges, instructions: found, exits, invalid, sources: vec![] }
+    }
+    /// # from_source
+    /// Assembles the code and builds its graph, starting at the first line of code. Only lines in the code segment are taken as code
+    /// and every address keeps the line of source it came from.
+    /// # Arguments
+    /// * `contents` - The assembly code
+    /// * `options` - The defines to assemble with
+    /// # Examples
+    /// ```
+    /// use vc_8bit::cfg::ControlFlowGraph;
+    /// use vc_8bit::assembly::AssemblyOptions;
+    /// let graph = ControlFlowGraph::from_source("MOV R0 1\nJMP_NEG end\nOUT R0\nend: HALT", &AssemblyOptions::default());
+    /// assert_eq!(graph.blocks.len(), 3);
+    /// assert_eq!(graph.sources[2].as_ref().unwrap().line, 2);
+    /// ```
+    pub fn from_source(contents: &str, options: &AssemblyOptions) -> ControlFlowGraph {
+        ControlFlowGraph::from_lines(assembly::preprocess_with_options(contents, options))
+    }
+    /// # from_file
+    /// Reads a file and builds its graph like `from_source`
+    pub fn from_file(path: &str, options: &AssemblyOptions) -> ControlFlowGraph {
+        ControlFlowGraph::from_lines(assembly::preprocess_file_with_options(path, options))
+    }
+    fn from_lines(lines: Vec<SourceLine>) -> ControlFlowGraph {
+        let code = assemble_code(lines);
+        let mut graph = ControlFlowGraph::with_code(&code.bytes, code.entry, &code.is_code);
+        graph.sources = code.sources;
+        graph
+    }
+    /// # to_dot
+    /// Writes the graph in the Graphviz DOT language, with a box for every block listing its instructions.
+    /// Conditional jumps are labeled with the mnemonic that tests the flag, like `JMP_ZRO`, and falling through to the next block is dashed.
+    /// # Arguments
+    /// * `options` - What to put in the graph
+    /// # Returns
+    /// * `String` - The DOT code, which can be drawn with `dot -Tsvg`
+    /// # Examples
+    /// ```
+    /// use vc_8bit::cfg::{ControlFlowGraph, DotOptions};
+    /// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+    /// let bytes = string_to_bytes(&compile_assembly_to_binary("loop: DEC R0\nJMP_ZRO end\nJMP loop\nend: HALT"));
+    /// let dot = ControlFlowGraph::new(&bytes, 0).to_dot(&DotOptions::default());
+    /// assert!(dot.contains("    b00 -> b05 [label=\"JMP_ZRO\"];\n"));
+    /// assert!(dot.contains("    b00 -> b03 [style=dashed];\n"));
+    /// ```
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let mut out = format!("digraph {} {{\n", dot_id(&options.name));
+        out += "    node [shape=box, fontname=\"monospace\"];\n";
+        out += "    entry [shape=point];\n";
+        for block in &self.blocks {
+            let mut label = String::new();
+            for instruction in &block.instructions {
+                let mut line = format!("{:02x}  {instruction}", instruction.address);
+                if let Some(Some(source)) = self.sources.get(instruction.address as usize).filter(|_| options.source_lines) {
+                    line = format!("{line:<20}; {:>3}: {}", source.line, source.text.trim());
+                }
+                label += &escape_label(&line);
+                label += "\\l";
+            }
+            out += &format!("    b{:02x} [label=\"{label}\"];\n", block.start);
+        }
+        if self.blocks.iter().any(|x| x.start == self.entry) {
+            out += &format!("    entry -> b{:02x};\n", self.entry);
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::FallThrough => " [style=dashed]".to_string(),
+                EdgeKind::Jump => String::new(),
+                EdgeKind::Conditional(mnemonic) => format!(" [label=\"{mnemonic}\"]"),
+            };
+            out += &format!("    b{:02x} -> b{:02x}{attributes};\n", edge.from, edge.to);
+        }
+        out + "}\n"
+    }
+}
+
+/// # DotOptions
+/// What `ControlFlowGraph::to_dot` puts in the graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotOptions {
+    /// The name of the graph
+    pub name: String,
+    /// Add the line number and text of the source next to every instruction, when the graph was built from source
+    pub source_lines: bool,
+}
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions { name: String::from("cfg"), source_lines: false }
+    }
+}
+
+/// Quotes a name for DOT unless it is a plain identifier
+fn dot_id(name: &str) -> String {
+    if !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        name.to_string()
+    }
+    else {
+        format!("\"{}\"", escape_label(name))
+    }
+}
+
+/// Escapes the characters that end or change a DOT string
+fn escape_label(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The bytes of an assembled program, which of them are code and the line of source each came from
+pub(crate) struct AssembledCode {
+    pub bytes: Vec<Byte>,
+    pub is_code: Vec<bool>,
+    pub sources: Vec<Option<SourceLine>>,
+    /// Every instruction in the code, whether it can run or not
+    pub instructions: Vec<DecodedInstruction>,
+    /// The address of the first byte of code
+    pub entry: i32,
+}
+
+/// Assembles the lines, keeping track of which bytes are code. `%BYTE` values, `%ORG` padding and the data segments are not code
+pub(crate) fn assemble_code(lines: Vec<SourceLine>) -> AssembledCode {
+    let program = assembly::assemble(lines);
+    let size = program.lines.iter().map(|x| x.address + x.binary.len() as i32 / 8).max().unwrap_or(0).max(0) as usize;
+    let mut bytes = vec![Byte::zero(); size];
+    let mut is_code = vec![false; size];
+    let mut sources: Vec<Option<SourceLine>> = vec![None; size];
+    let mut instructions: Vec<DecodedInstruction> = vec![];
+    for line in &program.lines {
+        let first = assembly::tokenize_line(&line.source.text).parts.first().map(|x| x.to_uppercase()).unwrap_or_default();
+        let code = line.segment == SegmentKind::Code && first != "%BYTE" && !first.ends_with("ORG");
+        for (i, byte) in assembly::string_to_bytes(&line.binary).into_iter().enumerate() {
+            let address = line.address as usize + i;
+            bytes[address] = byte;
+            is_code[address] = code;
+            sources[address] = Some(line.source.clone());
+        }
+        // the instructions of the line are read back so jumps into the middle of one can be found
+        let mut address = line.address;
+        while code && address < line.address + line.binary.len() as i32 / 8 {
+            match decode(&bytes, address) {
+                Some(instruction) => {
+                    address += instruction.size;
+                    instructions.push(instruction);
+                }
+                None => address += 1,
+            }
+        }
+    }
+    let entry = is_code.iter().position(|x| *x).unwrap_or(0) as i32;
+    AssembledCode { bytes, is_code, sources, instructions, entry }
+}
@@ -0,0 +1,158 @@
+use crate::assembly::{self, AsmLine};
+
+/// The case mnemonics and registers are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MnemonicCase {
+    #[default]
+    Upper,
+    Lower,
+    /// Leave them as they were written
+    Keep,
+}
+
+/// The base numeric literals are rewritten to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberBase {
+    /// `#00000101`
+    Binary,
+    /// `0x05`
+    Hex,
+    /// `5`
+    Decimal,
+}
+
+/// # FormatOptions
+/// How `format_assembly` lays out the code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub mnemonic_case: MnemonicCase,
+    /// The base numbers are rewritten to, `None` leaves them as they were written
+    pub number_base: Option<NumberBase>,
+    /// The number of spaces instructions are indented by
+    pub indent: usize,
+}
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { mnemonic_case: MnemonicCase::Upper, number_base: None, indent: 4 }
+    }
+}
+
+/// A line split up so the columns can be lined up
+enum FormatLine {
+    /// Directives, macros and blank lines are kept as they are
+    Verbatim(String),
+    /// A comment on its own line and whether it was indented
+    Comment(String, bool),
+    /// A label and/or an instruction with its comment
+    Code { label: Option<String>, mnemonic: Option<String>, operands: Vec<String>, comment: Option<String> },
+}
+
+/// # format_assembly
+/// Formats assembly code so every file has the same style.
+/// Mnemonics and registers are put in the same case, operands and `;` comments are lined up in columns and numbers can be
+/// rewritten to a single base. Labels stay at the start of the line and directives like `%ASSIGN` and `%MACRO` are kept as they are.
+/// Formatting code that is already formatted doesn't change it.
+/// # Arguments
+/// * `contents` - The assembly code
+/// * `options` - How to lay out the code
+/// # Returns
+/// * `String` - The formatted code
+/// # Examples
+/// ```
+/// use vc_8bit::formatter::{format_assembly, FormatOptions, NumberBase};
+/// let options = FormatOptions { number_base: Some(NumberBase::Hex), ..FormatOptions::default() };
+/// let code = "start: mov r0 10 ; ten\n  jmp_zro start   ; loop\nhalt";
+/// assert_eq!(format_assembly(code, &options), "start: MOV     R0 0x0a ; ten\n       JMP_ZRO start   ; loop\n       HALT\n");
+/// ```
+pub fn format_assembly(contents: &str, options: &FormatOptions) -> String {
+    let lines = contents.lines().map(|line| {
+        let tokens = assembly::tokenize_line(line);
+        match tokens.parts.first() {
+            Some(part) if part.starts_with('%') || part.starts_with('.') => FormatLine::Verbatim(line.trim_end().to_string()),
+            _ if tokens.label.is_none() && tokens.parts.is_empty() => match tokens.comment {
+                Some(comment) => FormatLine::Comment(comment, line.starts_with(char::is_whitespace)),
+                None => FormatLine::Verbatim(String::new()),
+            },
+            _ => code_line(tokens, options),
+        }
+    }).collect::<Vec<FormatLine>>();
+
+    // the mnemonics start after the longest label that shares a line with an instruction
+    let code_column = lines.iter().filter_map(|x| match x {
+        FormatLine::Code { label: Some(label), mnemonic: Some(_), .. } => Some(label.len() + 2),
+        _ => None,
+    }).max().unwrap_or(0).max(options.indent);
+    let mnemonic_width = lines.iter().filter_map(|x| match x {
+        FormatLine::Code { mnemonic: Some(mnemonic), operands, .. } if !operands.is_empty() => Some(mnemonic.len()),
+        _ => None,
+    }).max().unwrap_or(0);
+
+    let code = lines.iter().map(|x| match x {
+        FormatLine::Code { label, mnemonic, operands, .. } => {
+            let mut out = label.as_ref().map_or(String::new(), |x| format!("{x}:"));
+            if let Some(mnemonic) = mnemonic {
+                out = format!("{out:<code_column$}{mnemonic:<mnemonic_width$} {}", operands.join(" "));
+            }
+            out.trim_end().to_string()
+        }
+        _ => String::new(),
+    }).collect::<Vec<String>>();
+    let comment_column = lines.iter().zip(&code).filter_map(|(x, code)| match x {
+        FormatLine::Code { comment: Some(_), .. } => Some(code.len() + 1),
+        _ => None,
+    }).max().unwrap_or(0);
+
+    let mut out = String::new();
+    for (line, code) in lines.iter().zip(code) {
+        match line {
+            FormatLine::Verbatim(text) => out += text,
+            FormatLine::Comment(comment, indented) => out += &format!("{}{comment}", if *indented { " ".repeat(code_column) } else { String::new() }),
+            FormatLine::Code { comment: Some(comment), .. } => out += &format!("{code:<comment_column$}{comment}"),
+            FormatLine::Code { comment: None, .. } => out += &code,
+        }
+        out += "\n";
+    }
+    out
+}
+
+/// Puts a line with an instruction in the chosen case and base
+fn code_line(tokens: AsmLine, options: &FormatOptions) -> FormatLine {
+    let set_case = |value: &str| match options.mnemonic_case {
+        MnemonicCase::Upper => value.to_uppercase(),
+        MnemonicCase::Lower => value.to_lowercase(),
+        MnemonicCase::Keep => value.to_string(),
+    };
+    let mut parts = tokens.parts.iter();
+    let mnemonic = parts.next().map(|x| set_case(x));
+    let operands = parts.map(|x| {
+        if x.len() == 2 && (x.starts_with('R') || x.starts_with('r')) && x.ends_with(['0', '1', '2', '3']) {
+            set_case(x)
+        }
+        else {
+            rewrite_number(x, options.number_base)
+        }
+    }).collect();
+    FormatLine::Code { label: tokens.label, mnemonic, operands, comment: tokens.comment }
+}
+
+/// Writes a numeric literal in the base, anything else is left alone
+fn rewrite_number(value: &str, base: Option<NumberBase>) -> String {
+    let number = if let Some(hex) = value.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16).ok()
+    }
+    else if let Some(binary) = value.strip_prefix('#') {
+        u8::from_str_radix(binary, 2).ok()
+    }
+    else if value.chars().all(|c| c.is_ascii_digit()) {
+        value.parse::<u8>().ok()
+    }
+    else {
+        None
+    };
+    match (number, base) {
+        (Some(number), Some(NumberBase::Binary)) => format!("#{number:08b}"),
+        (Some(number), Some(NumberBase::Hex)) => format!("0x{number:02x}"),
+        (Some(number), Some(NumberBase::Decimal)) => number.to_string(),
+        _ => value.to_string(),
+    }
+}
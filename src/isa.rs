@@ -0,0 +1,264 @@
+/// The kinds of operands an instruction takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// `R0` to `R3`, 2 bits
+    Register,
+    /// A port address 0 to 7, 3 bits
+    Port,
+    /// A value or address, a whole byte
+    Value,
+}
+impl OperandKind {
+    /// returns the number of bits the operand takes
+    pub fn width(self) -> u8 {
+        match self {
+            OperandKind::Register => 2,
+            OperandKind::Port => 3,
+            OperandKind::Value => 8,
+        }
+    }
+}
+
+/// How an instruction uses an operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The value is only read
+    Read,
+    /// The value is only written, like the register `MOV` loads
+    Write,
+    /// The value is read and the result is written back, like the first register of `ADD`
+    ReadWrite,
+}
+impl Access {
+    /// returns if the instruction reads the operand
+    pub fn reads(self) -> bool {
+        matches!(self, Access::Read | Access::ReadWrite)
+    }
+    /// returns if the instruction writes the operand
+    pub fn writes(self) -> bool {
+        matches!(self, Access::Write | Access::ReadWrite)
+    }
+}
+
+/// An operand of an instruction and where its bits go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperandField {
+    pub kind: OperandKind,
+    /// The byte of the instruction the operand is in, 0 or 1
+    pub byte: usize,
+    /// How far the operand is shifted left in that byte
+    pub shift: u8,
+    /// If the instruction reads or writes the operand. Ports and values are always read
+    pub access: Access,
+}
+impl OperandField {
+    fn mask(&self) -> u8 {
+        (((1u16 << self.kind.width()) - 1) as u8) << self.shift
+    }
+}
+
+/// The ALU flags an instruction can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    /// The negative flag is on
+    Negative,
+    /// The zero flag is on
+    Zero,
+    /// Neither the negative or zero flag is on
+    Above,
+}
+
+/// # InstructionDef
+/// The definition of an instruction. The assembler, the VC and `disassemble` all read instructions from `INSTRUCTIONS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionDef {
+    pub mnemonic: &'static str,
+    /// The bits of the first byte that pick the instruction
+    pub opcode: u8,
+    /// Which bits of the first byte are part of the opcode
+    pub mask: u8,
+    /// 1 or 2 bytes
+    pub size: usize,
+    /// The operands in the order they are written
+    pub operands: &'static [OperandField],
+    /// If the instruction sets the negative and zero flags
+    pub sets_flags: bool,
+    /// The flag the instruction reads, if any
+    pub tests: Option<Flag>,
+}
+impl InstructionDef {
+    /// # encode
+    /// Puts the operands in the bytes of the instruction
+    /// # Arguments
+    /// * `operands` - The value of each operand, 0 for `R0`
+    /// # Examples
+    /// ```
+    /// use vc_8bit::isa;
+    /// assert_eq!(isa::lookup("MOV").unwrap().encode(&[1, 5]), vec![0b11001001, 5]);
+    /// assert_eq!(isa::lookup("CPY").unwrap().encode(&[3, 2]), vec![0b11001111, 0b10000000]);
+    /// ```
+    /// # Panics
+    /// Will panic if the number of operands is wrong or an operand doesn't fit in its bits
+    pub fn encode(&self, operands: &[i32]) -> Vec<u8> {
+        if operands.len() != self.operands.len() {
+            panic!("{} takes {} operands but {} were given", self.mnemonic, self.operands.len(), operands.len());
+        }
+        let mut bytes = vec![0u8; self.size];
+        bytes[0] = self.opcode;
+        for (field, value) in self.operands.iter().zip(operands) {
+            if *value < 0 || *value >= 1 << field.kind.width() {
+                panic!("{value} does not fit in the {:?} operand of {}", field.kind, self.mnemonic);
+            }
+            bytes[field.byte] |= (*value as u8) << field.shift;
+        }
+        bytes
+    }
+    /// # decode_operands
+    /// Reads the operands from the bytes of the instruction, a missing second byte reads as 0
+    pub fn decode_operands(&self, bytes: &[u8]) -> Vec<i32> {
+        self.operands.iter().map(|field| {
+            let byte = bytes.get(field.byte).copied().unwrap_or(0);
+            ((byte & field.mask()) >> field.shift) as i32
+        }).collect()
+    }
+    /// # format
+    /// Writes the instruction the way the assembler reads it, like `MOV R0 5`
+    pub fn format(&self, operands: &[i32]) -> String {
+        let mut parts = vec![self.mnemonic.to_string()];
+        for (field, value) in self.operands.iter().zip(operands) {
+            parts.push(match field.kind {
+                OperandKind::Register => format!("R{value}"),
+                _ => value.to_string(),
+            });
+        }
+        parts.join(" ")
+    }
+}
+
+const fn register(byte: usize, shift: u8, access: Access) -> OperandField {
+    OperandField { kind: OperandKind::Register, byte, shift, access }
+}
+const PORT: OperandField = OperandField { kind: OperandKind::Port, byte: 0, shift: 0, access: Access::Read };
+const VALUE: OperandField = OperandField { kind: OperandKind::Value, byte: 1, shift: 0, access: Access::Read };
+
+const fn def(mnemonic: &'static str, opcode: u8, mask: u8, size: usize, operands: &'static [OperandField], sets_flags: bool, tests: Option<Flag>) -> InstructionDef {
+    InstructionDef { mnemonic, opcode, mask, size, operands, sets_flags, tests }
+}
+
+/// # INSTRUCTIONS
+/// Every instruction the VC runs. No byte matches more than one of them, and the bytes that match none are not instructions.
+/// # Examples
+/// Every instruction comes back the same when it is encoded, decoded and assembled from text
+/// ```
+/// use vc_8bit::isa::{self, OperandKind, INSTRUCTIONS};
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// for definition in INSTRUCTIONS {
+///     let operands = definition.operands.iter().enumerate().map(|(i, x)| match x.kind {
+///         OperandKind::Register => 3 - i as i32,
+///         OperandKind::Port => 5,
+///         OperandKind::Value => 0xa5,
+///     }).collect::<Vec<i32>>();
+///     let bytes = definition.encode(&operands);
+///     assert_eq!(bytes.len(), definition.size);
+///     assert_eq!(isa::decode(&bytes), Some((definition, operands.clone())));
+///     let assembled = string_to_bytes(&compile_assembly_to_binary(&definition.format(&operands)));
+///     assert_eq!(assembled.iter().map(|x| x.to_i32() as u8).collect::<Vec<u8>>(), bytes);
+/// }
+/// for byte in 0..=255u8 {
+///     assert!(INSTRUCTIONS.iter().filter(|x| byte & x.mask == x.opcode).count() <= 1);
+/// }
+/// ```
+pub const INSTRUCTIONS: &[InstructionDef] = &[
+    def("ADD", 0b0000_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], true, None),
+    def("SUB", 0b0001_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], true, None),
+    def("MUL", 0b0010_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], true, None),
+    def("DIV", 0b0011_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], true, None),
+    def("AND", 0b0100_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], false, None),
+    def("OR", 0b0101_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], false, None),
+    def("NOT", 0b0110_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite)], false, None),
+    def("XOR", 0b0111_0000, 0b1111_0000, 1, &[register(0, 2, Access::ReadWrite), register(0, 0, Access::Read)], false, None),
+    def("RPRT", 0b1000_0000, 0b1110_0000, 1, &[register(0, 3, Access::Write), PORT], false, None),
+    def("WPRT", 0b1010_0000, 0b1110_0000, 1, &[register(0, 3, Access::Read), PORT], false, None),
+    def("STR", 0b1100_0000, 0b1111_1100, 2, &[register(0, 0, Access::Read), VALUE], false, None),
+    def("LDR", 0b1100_0100, 0b1111_1100, 2, &[register(0, 0, Access::Write), VALUE], false, None),
+    def("MOV", 0b1100_1000, 0b1111_1100, 2, &[register(0, 0, Access::Write), VALUE], false, None),
+    def("CPY", 0b1100_1100, 0b1111_1100, 2, &[register(0, 0, Access::Write), register(1, 6, Access::Read)], false, None),
+    def("SHL", 0b1101_0000, 0b1111_1100, 2, &[register(0, 0, Access::ReadWrite), VALUE], false, None),
+    def("SHR", 0b1101_0100, 0b1111_1100, 2, &[register(0, 0, Access::ReadWrite), VALUE], false, None),
+    def("OUT", 0b1101_1000, 0b1111_1100, 1, &[register(0, 0, Access::Read)], false, None),
+    def("MSG", 0b1101_1100, 0b1111_1100, 1, &[register(0, 0, Access::Read)], false, None),
+    def("INC", 0b1110_0000, 0b1111_1100, 1, &[register(0, 0, Access::ReadWrite)], false, None),
+    def("DEC", 0b1110_0100, 0b1111_1100, 1, &[register(0, 0, Access::ReadWrite)], false, None),
+    def("JMP", 0b1110_1000, 0b1111_1111, 2, &[VALUE], false, None),
+    def("JMP_NEG", 0b1110_1001, 0b1111_1111, 2, &[VALUE], false, Some(Flag::Negative)),
+    def("JMP_ZRO", 0b1110_1010, 0b1111_1111, 2, &[VALUE], false, Some(Flag::Zero)),
+    def("JMP_ABV", 0b1110_1011, 0b1111_1111, 2, &[VALUE], false, Some(Flag::Above)),
+    def("CMP_NEG", 0b1111_0000, 0b1111_1100, 1, &[register(0, 0, Access::Write)], false, Some(Flag::Negative)),
+    def("CMP_ZRO", 0b1111_0100, 0b1111_1100, 1, &[register(0, 0, Access::Write)], false, Some(Flag::Zero)),
+    def("CMP_ABV", 0b1111_1000, 0b1111_1100, 1, &[register(0, 0, Access::Write)], false, Some(Flag::Above)),
+    def("HALT", 0b1111_1111, 0b1111_1111, 1, &[], false, None),
+];
+
+/// # lookup
+/// Finds an instruction by its mnemonic, in any case
+/// # Examples
+/// ```
+/// use vc_8bit::isa;
+/// assert_eq!(isa::lookup("jmp_zro").unwrap().size, 2);
+/// assert_eq!(isa::lookup("CLR"), None);
+/// ```
+pub fn lookup(mnemonic: &str) -> Option<&'static InstructionDef> {
+    INSTRUCTIONS.iter().find(|x| x.mnemonic.eq_ignore_ascii_case(mnemonic))
+}
+
+/// # find
+/// Finds the instruction a byte starts
+pub fn find(byte: u8) -> Option<&'static InstructionDef> {
+    INSTRUCTIONS.iter().find(|x| byte & x.mask == x.opcode)
+}
+
+/// # decode
+/// Reads the instruction at the start of the bytes along with its operands. A missing second byte reads as 0
+/// # Returns
+/// * `Option<(&InstructionDef, Vec<i32>)>` - `None` if the bytes are empty or the first byte is not an instruction
+/// # Examples
+/// ```
+/// use vc_8bit::isa;
+/// let (definition, operands) = isa::decode(&[0b10110011]).unwrap();
+/// assert_eq!((definition.mnemonic, operands), ("WPRT", vec![2, 3]));
+/// ```
+pub fn decode(bytes: &[u8]) -> Option<(&'static InstructionDef, Vec<i32>)> {
+    let definition = find(*bytes.first()?)?;
+    Some((definition, definition.decode_operands(bytes)))
+}
+
+/// # disassemble
+/// Turns the bytes of a program back into assembly, one instruction a line. A byte that is not an instruction, or an
+/// instruction cut off at the end, is written as `%BYTE` so the text assembles to the same bytes
+/// # Examples
+/// ```
+/// use vc_8bit::isa;
+/// use vc_8bit::assembly::{compile_assembly_to_binary, string_to_bytes};
+/// let to_u8 = |text: &str| string_to_bytes(&compile_assembly_to_binary(text)).iter().map(|x| x.to_i32() as u8).collect::<Vec<u8>>();
+/// let bytes = to_u8("MOV R1 5\nloop:\nADD R0 R1\nWPRT R0 3\nJMP loop\n%BYTE 254\nHALT");
+/// let text = isa::disassemble(&bytes);
+/// assert_eq!(text, "MOV R1 5\nADD R0 R1\nWPRT R0 3\nJMP 2\n%BYTE 254\nHALT");
+/// assert_eq!(to_u8(&text), bytes);
+/// ```
+pub fn disassemble(bytes: &[u8]) -> String {
+    let mut lines = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match decode(&bytes[i..]) {
+            Some((definition, operands)) if i + definition.size <= bytes.len() => {
+                lines.push(definition.format(&operands));
+                i += definition.size;
+            }
+            _ => {
+                lines.push(format!("%BYTE {}", bytes[i]));
+                i += 1;
+            }
+        }
+    }
+    lines.join("\n")
+}
@@ -1,8 +1,19 @@
+pub mod analyzer;
 pub mod assembly;
+pub mod builder;
+pub mod c_check;
 pub mod c_lang;
+pub mod cfg;
+pub mod formatter;
+pub mod isa;
+pub mod linker;
+pub mod loader;
+pub mod vcimg;
 
 /// Defines the module for handling 8-bit version control operations.
 pub mod vc_8bit {
+    use crate::isa::{self, Flag};
+
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     // A single bit value stored as a boolean
     pub struct Bit {
@@ -322,6 +333,8 @@ pub mod vc_8bit {
         }
     }
 
+    /// The revision of the instruction set the VC runs, checked when loading a `.vcimg`
+    pub const ISA_REVISION: u8 = 1;
     /// A 256 byte RAM
     const RAM_SIZE: usize = 256;
     /// A 2 byte stream
@@ -348,6 +361,39 @@ pub mod vc_8bit {
                 self.write(Byte::try_from(i as i32).unwrap(), *byte);
             }
         }
+        /// # load_image
+        /// Loads a `.vcimg` file into the RAM and sets the index to its entry point
+        /// # Examples
+        /// ```
+        /// use vc_8bit::vc_8bit::{Byte, RAM};
+        /// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+        /// let program = assemble_program("%ORG 4\nMOV R0 5\nHALT", &AssemblyOptions::default());
+        /// let mut ram = RAM::new();
+        /// ram.load_image(&program.to_vcimg(true).to_bytes());
+        /// assert_eq!(ram.read(Byte::try_from(5).unwrap()).to_i32(), 5);
+        /// assert_eq!(ram.get_index().to_i32(), 4);
+        /// ```
+        /// # Panics
+        /// Will panic if the file is corrupt or was made for a different instruction set revision
+        pub fn load_image(&mut self, contents: &[u8]) {
+            let image = crate::vcimg::VcImage::from_bytes(contents);
+            if image.isa_revision != ISA_REVISION {
+                panic!("Image was made for ISA revision {} but the VC runs revision {ISA_REVISION}", image.isa_revision);
+            }
+            for segment in image.segments {
+                if segment.address + segment.bytes.len() as i32 > RAM_SIZE as i32 {
+                    panic!("Segment at {} does not fit in memory", segment.address);
+                }
+                self.insert_bytes_at(Byte::try_from(segment.address).unwrap(), segment.bytes);
+            }
+            self.set_index(Byte::try_from(image.entry).unwrap());
+        }
+        /// Inserts a vector of bytes into the RAM starting at the address
+        pub fn insert_bytes_at(&mut self, address: Byte, bytes: Vec<Byte>) {
+            for (i, byte) in bytes.iter().enumerate() {
+                self.write(Byte::try_from(address.to_i32() + i as i32).unwrap(), *byte);
+            }
+        }
         pub fn read(&self, address: Byte) -> Byte {
             self.value[address.to_i32() as usize].value
         }
@@ -833,451 +879,117 @@ pub mod vc_8bit {
         /// # Panics
         /// If the program contains an invalid instruction
         pub fn run_stream(&mut self, stream: [Byte; STREAM_SIZE]) -> bool {
-            let first_byte = stream[0].value;
-            let mut halted = false;
+            // the instruction and its operands are read using the definitions in isa::INSTRUCTIONS
+            let bytes = stream.map(|x| x.to_i32() as u8);
+            let (instruction, operands) = match isa::decode(&bytes) {
+                Some(decoded) => decoded,
+                None => panic!("Invalid function {}", stream[0].to_string()),
+            };
 
-            // 00 ALU 
-            // 01 Boolean
-            // 10 Ports
-            // 11 RAM 
-            
-            if !first_byte[0].value { // 0X XX XX XX
-                // ALU and logic takes 1 byte, Memory takes 2 bytes
-                // decrement so next instruction won't be skipped
+            // the stream takes 2 bytes from memory
+            // decrement for 1 byte instructions so the next instruction won't be skipped
+            if instruction.size == 1 {
                 self.ram.decrement();
+            }
 
-                if first_byte[1].value { // 01 XX XX XX
-                    // Boolean Logic
-                    let mut reg1_data: [u8; 2] = [0; 2];
-                    for i in 0..2 {
-                        reg1_data[i] = first_byte[4 + i].to_i32() as u8;
-                    } 
-                    let mut reg2_data: [u8; 2] = [0; 2];
-                    for i in 0..2 {
-                        reg2_data[i] = first_byte[6 + i].to_i32() as u8;
-                    } 
-                    let register1_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                    let register2_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg2_data[0] as i32).unwrap(), Bit::try_from(reg2_data[1] as i32).unwrap());
-                    let mut cpu = self.cpu.clone();
-                    let mut register1 = cpu.get_register(register1_address);
-                    let register2 = cpu.get_register(register2_address);
-                    match stream[0].to_u8_array() {
-                        [0, 1, 0, 0, _, _, _, _] => {
-                            // AND
-                            register1.write(register1.value.and(&register2.value));
-                        }
-                        [0, 1, 0, 1, _, _, _, _] => {
-                            // OR
-                            register1.write(register1.value.or(&register2.value));
-                        }
-                        [0, 1, 1, 0, _, _, _, _] => {
-                            // NOT
-                            register1.write(register1.value.not());
-                        }
-                        [0, 1, 1, 1, _, _, _, _] => {
-                            // XOR
-                            register1.write(register1.value.xor(&register2.value));
-                        }
-                        _ => {
-                            panic!("Invalid boolean logic instruction");
-                        }
-                    }
-                    match register1_address {
-                        1 => cpu.reg_1 = register1,
-                        2 => cpu.reg_2 = register1,
-                        3 => cpu.reg_3 = register1,
-                        4 => cpu.reg_4 = register1,
-                        _ => panic!("Invalid register address"),
-                    }
-                    self.cpu = cpu;
-                }
-                else { // 00 XX XX XX
-                    // ALU
-                    let op1_a = first_byte[4].to_i32();
-                    let op1_b = first_byte[5].to_i32();
-                    let op2_a = first_byte[6].to_i32();
-                    let op2_b = first_byte[7].to_i32();
-                    let register_address1 = BinaryDecoder::decode_internal_i32(Bit::try_from(op1_a).unwrap(), Bit::try_from(op1_b).unwrap());
-                    let register_address2 = BinaryDecoder::decode_internal_i32(Bit::try_from(op2_a).unwrap(), Bit::try_from(op2_b).unwrap());
-                    let operand_1 = self.cpu.get_register(register_address1).value.to_i32();
-                    let operand_2 = self.cpu.get_register(register_address2).value.to_i32();
-        
-                    self.cpu.alu.value1 = Byte::try_from(operand_1).unwrap();
-                    self.cpu.alu.value2 = Byte::try_from(operand_2).unwrap();
-                    self.cpu.alu.decoder = BinaryDecoder {axis_x: first_byte[2], axis_y: first_byte[3], result: [Bit::new(false); 4]};
+            // registers R0 to R3 are 1 to 4 in the CPU
+            let register = |i: usize| operands[i] + 1;
+            let value = |i: usize| Byte::try_from(operands[i]).unwrap();
+            let flag = |flag: Flag, math: &ByteArithmetic| match flag {
+                Flag::Negative => math.neg.to_bool(),
+                Flag::Zero => math.zero.to_bool(),
+                Flag::Above => !math.neg.to_bool() && !math.zero.to_bool(),
+            };
+            match instruction.mnemonic {
+                "ADD" | "SUB" | "MUL" | "DIV" => {
+                    // the last 2 bits of the opcode pick the operation in the ALU
+                    self.cpu.alu.value1 = self.cpu.get_register(register(0)).value;
+                    self.cpu.alu.value2 = self.cpu.get_register(register(1)).value;
+                    self.cpu.alu.decoder = BinaryDecoder { axis_x: stream[0].value[2], axis_y: stream[0].value[3], result: [Bit::new(false); 4] };
                     self.cpu.alu.compute();
-        
-                    let mut cpu = self.cpu.clone();
-                    let mut register = cpu.get_register(register_address1);
-                    register.write(Byte::try_from(self.cpu.alu.value1).unwrap());
-                    match register_address1 {
-                        1 => cpu.reg_1 = register,
-                        2 => cpu.reg_2 = register,
-                        3 => cpu.reg_3 = register,
-                        4 => cpu.reg_4 = register,
-                        _ => panic!("Invalid register address"),
-                    }
-                    self.cpu = cpu;
+                    self.write_register(register(0), self.cpu.alu.value1);
                 }
-            }
-            else { // 1X XX XX XX
-                if !first_byte[1].value { // 10 XX XX XX
-                    // Ports
-
-                    // Ports takes 1 byte, Memory takes 2 bytes
-                    // decrement so next instruction won't be skipped
-                    self.ram.decrement();
-
-                    let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(first_byte[3].value as i32).unwrap(), Bit::try_from(first_byte[4].value as i32).unwrap());
-
-                    let port = [first_byte[5].value, first_byte[6].value, first_byte[7].value];
-                    let port_address: i32 = match port {
-                        [false, false, false] => 0,
-                        [false, false, true] => 1,
-                        [false, true, false] => 2,
-                        [false, true, true] => 3,
-                        [true, false, false] => 4,
-                        [true, false, true] => 5,
-                        [true, true, false] => 6,
-                        [true, true, true] => 7,
-                    };
-                    
-                    if !first_byte[2].value { // 10 0X XX XX
-                        // read
-                        let mut cpu = self.cpu.clone();
-                        let mut register = cpu.get_register(register_address);
-                        let value = self.ports.read(port_address.try_into().unwrap());
-                        register.write(value);
-                        match register_address {
-                            1 => cpu.reg_1 = register,
-                            2 => cpu.reg_2 = register,
-                            3 => cpu.reg_3 = register,
-                            4 => cpu.reg_4 = register,
-                            _ => panic!("Invalid register address"),
-                        }
-                        self.cpu = cpu;
-                    }
-                    else { // 10 1X XX XX
-                        // write
-                        let register = self.cpu.get_register(register_address);
-                        _ = self.ports.write(port_address.try_into().unwrap(), register.value);
-                    }
-                } 
-                else { // 11 XX XX XX
-                    match stream[0].to_u8_array() {
-                        [1, 1, 1, 1, 1, 1, 1, 1] => {
-                            // Halt
-                
-                            // Halting takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            // stop running
-                            halted = true;
-                        }
-                        [1, 1, 0, 0, 0, 0, _, _] => {
-                            // Store
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let address = stream[1];
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let data = self.cpu.get_register(register_address);
-                            self.ram.write(address, data.value);
-                        }
-                        [1, 1, 0, 0, 0, 1, _, _] => {
-                            // Load
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let address = stream[1];
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write(self.ram.read(address));
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 0, 0, 1, 0, _, _] => {
-                            // Move
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let binary: Byte = stream[1];
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write(binary);
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 0, 0, 1, 1, _, _] => {
-                            // Copy
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let mut reg2_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg2_data[i] = stream[1].value[i].to_i32() as u8;
-                            } 
-                            let register1_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let register2_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg2_data[0] as i32).unwrap(), Bit::try_from(reg2_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register1 = cpu.get_register(register1_address);
-                            let register2 = cpu.get_register(register2_address);
-                            register1.write(register2.value);
-                            match register1_address {
-                                1 => cpu.reg_1 = register1,
-                                2 => cpu.reg_2 = register1,
-                                3 => cpu.reg_3 = register1,
-                                4 => cpu.reg_4 = register1,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 0, 1, 0, 0, _, _] => {
-                            // Shift Left
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let binary: Byte = stream[1];
-                            let register1_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register1 = cpu.get_register(register1_address);
-                            register1.write(Byte::shift_array(register1.value, -binary.to_i32()));
-                            match register1_address {
-                                1 => cpu.reg_1 = register1,
-                                2 => cpu.reg_2 = register1,
-                                3 => cpu.reg_3 = register1,
-                                4 => cpu.reg_4 = register1,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 0, 1, 0, 1, _, _] => {
-                            // Shift Right
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let binary: Byte = stream[1];
-                            let register1_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register1 = cpu.get_register(register1_address);
-                            register1.write(Byte::shift_array(register1.value, binary.to_i32()));
-                            match register1_address {
-                                1 => cpu.reg_1 = register1,
-                                2 => cpu.reg_2 = register1,
-                                3 => cpu.reg_3 = register1,
-                                4 => cpu.reg_4 = register1,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 0, 1, 1, 0, _, _] => {
-                            // Out
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let value = self.cpu.get_register(register_address).value;
-                            print!("{}", value.to_string());
-                        }
-                        [1, 1, 0, 1, 1, 1, _, _] => {
-                            // Msg
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let value = self.cpu.get_register(register_address).value;
-                            let c = bool_array_to_ascii(value.to_bool_array());
-                            print!("{}", c);
-                        }
-                        [1, 1, 1, 0, 0, 0, _, _] => {
-                            // Increment
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write((register.value.to_i32() + 1).try_into().unwrap());
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 1, 0, 0, 1, _, _] => {
-                            // Decrement
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg1_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg1_data[i] = first_byte[6 + i].to_i32() as u8;
-                            } 
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg1_data[0] as i32).unwrap(), Bit::try_from(reg1_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write((register.value.to_i32() - 1).try_into().unwrap());
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 1, 0, 1, 0, 0, 0] => {
-                            // Jump
-                            let binary = stream[1];
-                            self.ram.set_index(binary);
-                        }
-                        [1, 1, 1, 0, 1, 0, 0, 1] => {
-                            // Jump Neg
-                            let binary = stream[1];
-                            if self.cpu.alu.math.neg.to_bool() {
-                                self.ram.set_index(binary);
-                            }
-                        }
-                        [1, 1, 1, 0, 1, 0, 1, 0] => {
-                            // Jump Zero
-                            let binary = stream[1];
-                            if self.cpu.alu.math.zero.to_bool() {
-                                self.ram.set_index(binary);
-                            }
-                        }
-                        [1, 1, 1, 0, 1, 0, 1, 1] => {
-                            // Jump Above
-                            let binary = stream[1];
-                            if !self.cpu.alu.math.neg.to_bool() && !self.cpu.alu.math.zero.to_bool() {
-                                self.ram.set_index(binary);
-                            }
-                        }
-                        [1, 1, 1, 1, 0, 0, _, _] => {
-                            // CMP_NEG
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-                            
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            }
-                            let binary = if self.cpu.alu.math.neg.to_bool() { Byte::full() } else { Byte::zero() };
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write(binary);
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 1, 1, 0, 1, _, _] => {
-                            // CMP_ZRO
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            }
-                            let binary = if self.cpu.alu.math.zero.to_bool() { Byte::full() } else { Byte::zero() };
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write(binary);
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        [1, 1, 1, 1, 1, 0, _, _] => {
-                            // CMP_ABV
-
-                            // only takes 1 byte from memory
-                            // decrement so next instruction won't be skipped
-                            self.ram.decrement();
-
-                            let mut reg_data: [u8; 2] = [0; 2];
-                            for i in 0..2 {
-                                reg_data[i] = first_byte[6 + i].to_i32() as u8;
-                            }
-                            let binary = if !self.cpu.alu.math.neg.to_bool() && !self.cpu.alu.math.zero.to_bool() { Byte::full() } else { Byte::zero() };
-                            let register_address = BinaryDecoder::decode_internal_i32(Bit::try_from(reg_data[0] as i32).unwrap(), Bit::try_from(reg_data[1] as i32).unwrap());
-                            let mut cpu = self.cpu.clone();
-                            let mut register = cpu.get_register(register_address);
-                            register.write(binary);
-                            match register_address {
-                                1 => cpu.reg_1 = register,
-                                2 => cpu.reg_2 = register,
-                                3 => cpu.reg_3 = register,
-                                4 => cpu.reg_4 = register,
-                                _ => panic!("Invalid register address"),
-                            }
-                            self.cpu = cpu;
-                        }
-                        _ => {
-                            panic!("Invalid function {}", stream[0].to_string());
-                        }
+                "AND" => {
+                    let result = self.cpu.get_register(register(0)).value.and(&self.cpu.get_register(register(1)).value);
+                    self.write_register(register(0), result);
+                }
+                "OR" => {
+                    let result = self.cpu.get_register(register(0)).value.or(&self.cpu.get_register(register(1)).value);
+                    self.write_register(register(0), result);
+                }
+                "NOT" => {
+                    let result = self.cpu.get_register(register(0)).value.not();
+                    self.write_register(register(0), result);
+                }
+                "XOR" => {
+                    let result = self.cpu.get_register(register(0)).value.xor(&self.cpu.get_register(register(1)).value);
+                    self.write_register(register(0), result);
+                }
+                "RPRT" => {
+                    let result = self.ports.read(value(1));
+                    self.write_register(register(0), result);
+                }
+                "WPRT" => {
+                    _ = self.ports.write(value(1), self.cpu.get_register(register(0)).value);
+                }
+                "STR" => {
+                    let data = self.cpu.get_register(register(0)).value;
+                    self.ram.write(value(1), data);
+                }
+                "LDR" => {
+                    let data = self.ram.read(value(1));
+                    self.write_register(register(0), data);
+                }
+                "MOV" => self.write_register(register(0), value(1)),
+                "CPY" => {
+                    let data = self.cpu.get_register(register(1)).value;
+                    self.write_register(register(0), data);
+                }
+                "SHL" => {
+                    let result = Byte::shift_array(self.cpu.get_register(register(0)).value, -operands[1]);
+                    self.write_register(register(0), result);
+                }
+                "SHR" => {
+                    let result = Byte::shift_array(self.cpu.get_register(register(0)).value, operands[1]);
+                    self.write_register(register(0), result);
+                }
+                "OUT" => print!("{}", self.cpu.get_register(register(0)).value.to_string()),
+                "MSG" => print!("{}", bool_array_to_ascii(self.cpu.get_register(register(0)).value.to_bool_array())),
+                "INC" => {
+                    let result = (self.cpu.get_register(register(0)).value.to_i32() + 1).try_into().unwrap();
+                    self.write_register(register(0), result);
+                }
+                "DEC" => {
+                    let result = (self.cpu.get_register(register(0)).value.to_i32() - 1).try_into().unwrap();
+                    self.write_register(register(0), result);
+                }
+                "JMP" => self.ram.set_index(value(0)),
+                "JMP_NEG" | "JMP_ZRO" | "JMP_ABV" => {
+                    if flag(instruction.tests.unwrap(), &self.cpu.alu.math) {
+                        self.ram.set_index(value(0));
                     }
                 }
+                "CMP_NEG" | "CMP_ZRO" | "CMP_ABV" => {
+                    // the register is set to all 1s if the flag is on
+                    let result = if flag(instruction.tests.unwrap(), &self.cpu.alu.math) { Byte::full() } else { Byte::zero() };
+                    self.write_register(register(0), result);
+                }
+                "HALT" => return true,
+                _ => panic!("Invalid function {}", stream[0].to_string()),
+            }
+            false
+        }
+        /// writes a value to register 1 through 4
+        fn write_register(&mut self, address: i32, value: Byte) {
+            let mut register = self.cpu.get_register(address);
+            register.write(value);
+            match address {
+                1 => self.cpu.reg_1 = register,
+                2 => self.cpu.reg_2 = register,
+                3 => self.cpu.reg_3 = register,
+                4 => self.cpu.reg_4 = register,
+                _ => panic!("Invalid register address"),
             }
-            halted
         }
     }
 
@@ -1292,4 +1004,200 @@ pub mod vc_8bit {
 
         value as char
     }
-}
\ No newline at end of file
+
+    /// A register of the CPU, as it is written in assembly
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum Reg {
+        R0,
+        R1,
+        R2,
+        R3,
+    }
+    impl Reg {
+        /// returns the number of the register, 0 for R0
+        pub fn index(self) -> i32 {
+            self as i32
+        }
+        /// returns the register with the number, 0 for R0
+        pub fn from_index(index: i32) -> Option<Reg> {
+            match index {
+                0 => Some(Reg::R0),
+                1 => Some(Reg::R1),
+                2 => Some(Reg::R2),
+                3 => Some(Reg::R3),
+                _ => None,
+            }
+        }
+    }
+    impl std::fmt::Display for Reg {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "R{}", self.index())
+        }
+    }
+
+    /// # Instruction
+    /// An instruction of the VC. The encodings come from `isa::INSTRUCTIONS`, so they are the same as the assembler's.
+    /// `Display` writes the instruction the way the assembler reads it.
+    /// # Examples
+    /// ```
+    /// use vc_8bit::vc_8bit::{Instruction, Reg};
+    /// use vc_8bit::isa::Flag;
+    /// let program = [Instruction::Mov { dst: Reg::R0, imm: 5 }, Instruction::Jmp { cond: Some(Flag::Zero), addr: 9 }, Instruction::Halt];
+    /// let bytes = program.iter().flat_map(|x| x.encode()).collect::<Vec<_>>();
+    /// assert_eq!(bytes.len(), 5);
+    /// assert_eq!(program[1].to_string(), "JMP_ZRO 9");
+    /// assert_eq!(Instruction::decode(&bytes[2..]), Ok((program[1], 2)));
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Instruction {
+        Add { dst: Reg, src: Reg },
+        Sub { dst: Reg, src: Reg },
+        Mul { dst: Reg, src: Reg },
+        Div { dst: Reg, src: Reg },
+        And { dst: Reg, src: Reg },
+        Or { dst: Reg, src: Reg },
+        Not { reg: Reg },
+        Xor { dst: Reg, src: Reg },
+        /// Reads port 0 to 7 into the register
+        Rprt { reg: Reg, port: u8 },
+        /// Writes the register to port 0 to 7
+        Wprt { reg: Reg, port: u8 },
+        Str { src: Reg, addr: u8 },
+        Ldr { dst: Reg, addr: u8 },
+        Mov { dst: Reg, imm: u8 },
+        Cpy { dst: Reg, src: Reg },
+        Shl { reg: Reg, amount: u8 },
+        Shr { reg: Reg, amount: u8 },
+        Out { reg: Reg },
+        Msg { reg: Reg },
+        Inc { reg: Reg },
+        Dec { reg: Reg },
+        /// `JMP` when `cond` is `None`, otherwise the jump that tests the flag
+        Jmp { cond: Option<Flag>, addr: u8 },
+        /// Sets the register to all 1s if the flag is on and 0 if it isn't
+        Cmp { flag: Flag, reg: Reg },
+        Halt,
+    }
+    impl Instruction {
+        /// # decode
+        /// Reads the instruction at the start of the bytes
+        /// # Arguments
+        /// * `bytes` - The bytes of the program starting at the instruction
+        /// # Returns
+        /// * `Result<(Instruction, usize), String>` - The instruction and the number of bytes it takes,
+        ///   or an error if the bytes are not an instruction or are cut short
+        /// # Examples
+        /// ```
+        /// use vc_8bit::vc_8bit::{Byte, Instruction, Reg};
+        /// let bytes = [Byte::from_u8(0b11001101), Byte::from_u8(0b01000000)];
+        /// assert_eq!(Instruction::decode(&bytes), Ok((Instruction::Cpy { dst: Reg::R1, src: Reg::R1 }, 2)));
+        /// assert!(Instruction::decode(&bytes[..1]).is_err());
+        /// assert!(Instruction::decode(&[Byte::from_u8(0b11101100)]).is_err());
+        /// // every instruction comes back the same after encoding it
+        /// for byte in 0..=255u8 {
+        ///     if let Ok((instruction, _)) = Instruction::decode(&[Byte::from_u8(byte), Byte::from_u8(0b01000000)]) {
+        ///         assert_eq!(Instruction::decode(&instruction.encode()), Ok((instruction, instruction.size())));
+        ///     }
+        /// }
+        /// ```
+        pub fn decode(bytes: &[Byte]) -> Result<(Instruction, usize), String> {
+            let stream = bytes.iter().take(STREAM_SIZE).map(|x| x.to_i32() as u8).collect::<Vec<u8>>();
+            let (definition, operands) = match isa::decode(&stream) {
+                Some(decoded) => decoded,
+                None if stream.is_empty() => return Err(String::from("No bytes to decode")),
+                None => return Err(format!("{} is not an instruction", bytes[0].to_string())),
+            };
+            if stream.len() < definition.size {
+                return Err(format!("{} takes {} bytes but only {} are left", definition.mnemonic, definition.size, stream.len()));
+            }
+            let reg = |i: usize| Reg::from_index(operands[i]).unwrap();
+            let value = |i: usize| operands[i] as u8;
+            let instruction = match definition.mnemonic {
+                "ADD" => Instruction::Add { dst: reg(0), src: reg(1) },
+                "SUB" => Instruction::Sub { dst: reg(0), src: reg(1) },
+                "MUL" => Instruction::Mul { dst: reg(0), src: reg(1) },
+                "DIV" => Instruction::Div { dst: reg(0), src: reg(1) },
+                "AND" => Instruction::And { dst: reg(0), src: reg(1) },
+                "OR" => Instruction::Or { dst: reg(0), src: reg(1) },
+                "NOT" => Instruction::Not { reg: reg(0) },
+                "XOR" => Instruction::Xor { dst: reg(0), src: reg(1) },
+                "RPRT" => Instruction::Rprt { reg: reg(0), port: value(1) },
+                "WPRT" => Instruction::Wprt { reg: reg(0), port: value(1) },
+                "STR" => Instruction::Str { src: reg(0), addr: value(1) },
+                "LDR" => Instruction::Ldr { dst: reg(0), addr: value(1) },
+                "MOV" => Instruction::Mov { dst: reg(0), imm: value(1) },
+                "CPY" => Instruction::Cpy { dst: reg(0), src: reg(1) },
+                "SHL" => Instruction::Shl { reg: reg(0), amount: value(1) },
+                "SHR" => Instruction::Shr { reg: reg(0), amount: value(1) },
+                "OUT" => Instruction::Out { reg: reg(0) },
+                "MSG" => Instruction::Msg { reg: reg(0) },
+                "INC" => Instruction::Inc { reg: reg(0) },
+                "DEC" => Instruction::Dec { reg: reg(0) },
+                "JMP" | "JMP_NEG" | "JMP_ZRO" | "JMP_ABV" => Instruction::Jmp { cond: definition.tests, addr: value(0) },
+                "CMP_NEG" | "CMP_ZRO" | "CMP_ABV" => Instruction::Cmp { flag: definition.tests.unwrap(), reg: reg(0) },
+                "HALT" => Instruction::Halt,
+                mnemonic => return Err(format!("{mnemonic} has no Instruction")),
+            };
+            Ok((instruction, definition.size))
+        }
+        /// # encode
+        /// Writes the instruction as bytes
+        /// # Examples
+        /// ```
+        /// use vc_8bit::vc_8bit::{Instruction, Reg};
+        /// let bytes = Instruction::Rprt { reg: Reg::R2, port: 3 }.encode();
+        /// assert_eq!(bytes.iter().map(|x| x.to_string()).collect::<String>(), "10010011");
+        /// ```
+        /// # Panics
+        /// Will panic if a port is not 0 to 7
+        pub fn encode(&self) -> Vec<Byte> {
+            let (definition, operands) = self.definition();
+            definition.encode(&operands).into_iter().map(Byte::from_u8).collect()
+        }
+        /// returns the number of bytes the instruction takes
+        pub fn size(&self) -> usize {
+            self.definition().0.size
+        }
+        /// Gets the entry in `isa::INSTRUCTIONS` and the operands in the order they are written
+        fn definition(&self) -> (&'static isa::InstructionDef, Vec<i32>) {
+            let (mnemonic, operands) = match *self {
+                Instruction::Add { dst, src } => ("ADD", vec![dst.index(), src.index()]),
+                Instruction::Sub { dst, src } => ("SUB", vec![dst.index(), src.index()]),
+                Instruction::Mul { dst, src } => ("MUL", vec![dst.index(), src.index()]),
+                Instruction::Div { dst, src } => ("DIV", vec![dst.index(), src.index()]),
+                Instruction::And { dst, src } => ("AND", vec![dst.index(), src.index()]),
+                Instruction::Or { dst, src } => ("OR", vec![dst.index(), src.index()]),
+                Instruction::Not { reg } => ("NOT", vec![reg.index()]),
+                Instruction::Xor { dst, src } => ("XOR", vec![dst.index(), src.index()]),
+                Instruction::Rprt { reg, port } => ("RPRT", vec![reg.index(), port as i32]),
+                Instruction::Wprt { reg, port } => ("WPRT", vec![reg.index(), port as i32]),
+                Instruction::Str { src, addr } => ("STR", vec![src.index(), addr as i32]),
+                Instruction::Ldr { dst, addr } => ("LDR", vec![dst.index(), addr as i32]),
+                Instruction::Mov { dst, imm } => ("MOV", vec![dst.index(), imm as i32]),
+                Instruction::Cpy { dst, src } => ("CPY", vec![dst.index(), src.index()]),
+                Instruction::Shl { reg, amount } => ("SHL", vec![reg.index(), amount as i32]),
+                Instruction::Shr { reg, amount } => ("SHR", vec![reg.index(), amount as i32]),
+                Instruction::Out { reg } => ("OUT", vec![reg.index()]),
+                Instruction::Msg { reg } => ("MSG", vec![reg.index()]),
+                Instruction::Inc { reg } => ("INC", vec![reg.index()]),
+                Instruction::Dec { reg } => ("DEC", vec![reg.index()]),
+                Instruction::Jmp { cond: None, addr } => ("JMP", vec![addr as i32]),
+                Instruction::Jmp { cond: Some(Flag::Negative), addr } => ("JMP_NEG", vec![addr as i32]),
+                Instruction::Jmp { cond: Some(Flag::Zero), addr } => ("JMP_ZRO", vec![addr as i32]),
+                Instruction::Jmp { cond: Some(Flag::Above), addr } => ("JMP_ABV", vec![addr as i32]),
+                Instruction::Cmp { flag: Flag::Negative, reg } => ("CMP_NEG", vec![reg.index()]),
+                Instruction::Cmp { flag: Flag::Zero, reg } => ("CMP_ZRO", vec![reg.index()]),
+                Instruction::Cmp { flag: Flag::Above, reg } => ("CMP_ABV", vec![reg.index()]),
+                Instruction::Halt => ("HALT", vec![]),
+            };
+            (isa::lookup(mnemonic).unwrap(), operands)
+        }
+    }
+    impl std::fmt::Display for Instruction {
+        /// Writes the instruction the way the assembler reads it, like `MOV R0 5`
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let (definition, operands) = self.definition();
+            write!(f, "{}", definition.format(&operands))
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::assembly::{self, AssembledProgram, SymbolKind};
+use crate::vc_8bit::Byte;
+
+/// A name a module makes available to other modules with `%GLOBAL`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    /// The value of the symbol, for labels this is the address inside the module
+    pub value: i32,
+    /// If the value is an address that moves with the module, constants keep their value
+    pub relocatable: bool,
+}
+
+/// A byte in the code of a module that holds an address which has to be patched when the modules are linked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// Where the byte is, counted from the start of the module
+    pub offset: usize,
+    /// The `%EXTERN` name the byte refers to, or `None` for an address inside the module
+    pub symbol: Option<String>,
+}
+
+/// # ObjectModule
+/// An assembled module that still has to be linked with the other modules of the program.
+/// Modules without an `%ORG` before their code can be placed anywhere by the linker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectModule {
+    pub name: String,
+    pub code: Vec<Byte>,
+    /// The fixed address of the module, set with `%ORG`
+    pub origin: Option<i32>,
+    pub exports: Vec<Export>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+impl ObjectModule {
+    /// # from_program
+    /// Creates an object module from an assembled program
+    /// # Arguments
+    /// * `name` - The name of the module, shown in the memory map
+    /// * `program` - The assembled program
+    /// # Examples
+    /// ```
+    /// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+    /// use vc_8bit::linker::ObjectModule;
+    /// let program = assemble_program("%EXTERN print\nstart: JMP print\nJMP start", &AssemblyOptions::default());
+    /// let module = ObjectModule::from_program("main", &program);
+    /// assert_eq!(module.imports, vec!["print"]);
+    /// assert_eq!(module.relocations.len(), 2);
+    /// ```
+    pub fn from_program(name: &str, program: &AssembledProgram) -> ObjectModule {
+        let origin = program.origin.unwrap_or(0);
+        let mut relocations: Vec<Relocation> = vec![];
+        for line in &program.lines {
+            let instructions = if line.expansion.is_empty() {
+                vec![line.source.text.clone()]
+            }
+            else {
+                line.expansion.iter().map(|x| x.0.clone()).collect()
+            };
+            let mut address = line.address;
+            let parts = assembly::tokenize_line(&line.source.text).parts;
+            if parts.first().is_some_and(|x| x.to_uppercase() == "%BYTE") {
+                // every value of a %BYTE line is its own byte
+                let operands = parts[1..].iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+                for (i, operand) in assembly::data_values(&operands, &line.source).iter().enumerate() {
+                    match program.symbols.iter().find(|x| &x.name == operand).map(|x| &x.kind) {
+                        Some(SymbolKind::Label) => relocations.push(Relocation { offset: (address - origin) as usize + i, symbol: None }),
+                        Some(SymbolKind::Extern) => relocations.push(Relocation { offset: (address - origin) as usize + i, symbol: Some(operand.clone()) }),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            for instruction in instructions {
+                let parts = assembly::tokenize_line(&instruction).parts;
+                let size = parts.first().map_or(0, |x| assembly::instruction_size(x));
+                // the address is always the second byte of a 2 byte instruction
+                if let (2, Some(operand)) = (size, parts.last()) {
+                    match program.symbols.iter().find(|x| &x.name == operand).map(|x| &x.kind) {
+                        Some(SymbolKind::Label) => relocations.push(Relocation { offset: (address - origin + 1) as usize, symbol: None }),
+                        Some(SymbolKind::Extern) => relocations.push(Relocation { offset: (address - origin + 1) as usize, symbol: Some(operand.clone()) }),
+                        _ => {}
+                    }
+                }
+                address += size;
+            }
+        }
+        ObjectModule {
+            name: name.to_string(),
+            code: assembly::string_to_bytes(&program.code()),
+            origin: program.origin,
+            exports: program.symbols.iter().filter(|x| x.global).map(|x| Export { name: x.name.clone(), value: x.value.to_i32(), relocatable: x.kind == SymbolKind::Label }).collect(),
+            imports: program.symbols.iter().filter(|x| x.kind == SymbolKind::Extern).map(|x| x.name.clone()).collect(),
+            relocations,
+        }
+    }
+    /// # to_text
+    /// Writes the module in the text object format read by `ObjectModule::from_text`
+    /// ```text
+    /// VCOBJ 1
+    /// NAME main
+    /// ORG 16
+    /// CODE 11101000 00000000
+    /// EXPORT start 0 LABEL
+    /// IMPORT print
+    /// RELOC 1 print
+    /// ```
+    /// `ORG` is only written for modules with a fixed address and `RELOC` lines without a name are addresses inside the module.
+    pub fn to_text(&self) -> String {
+        let mut out = format!("VCOBJ 1\nNAME {}\n", self.name);
+        if let Some(origin) = self.origin {
+            out += format!("ORG {origin}\n").as_str();
+        }
+        out += format!("CODE {}\n", self.code.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(" ")).as_str();
+        for export in &self.exports {
+            out += format!("EXPORT {} {} {}\n", export.name, export.value, if export.relocatable { "LABEL" } else { "CONSTANT" }).as_str();
+        }
+        for import in &self.imports {
+            out += format!("IMPORT {import}\n").as_str();
+        }
+        for relocation in &self.relocations {
+            out += format!("RELOC {} {}", relocation.offset, relocation.symbol.clone().unwrap_or_default()).trim_end();
+            out += "\n";
+        }
+        out
+    }
+    /// # from_text
+    /// Reads a module written by `ObjectModule::to_text`
+    /// # Examples
+    /// ```
+    /// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+    /// use vc_8bit::linker::ObjectModule;
+    /// let program = assemble_program("%GLOBAL start\nstart: JMP start", &AssemblyOptions::default());
+    /// let module = ObjectModule::from_program("main", &program);
+    /// assert_eq!(ObjectModule::from_text(&module.to_text()), module);
+    /// ```
+    /// # Panics
+    /// Will panic if the text is not a valid object module
+    pub fn from_text(contents: &str) -> ObjectModule {
+        let mut lines = contents.lines();
+        if lines.next().map(|x| x.trim()) != Some("VCOBJ 1") {
+            panic!("Not an object module, expected 'VCOBJ 1' in line 1");
+        }
+        let mut module = ObjectModule { name: String::new(), code: vec![], origin: None, exports: vec![], imports: vec![], relocations: vec![] };
+        for (i, line) in lines.enumerate() {
+            let parts = line.split_whitespace().collect::<Vec<&str>>();
+            let number = |part: Option<&&str>| match part.and_then(|x| x.parse::<i32>().ok()) {
+                Some(value) => value,
+                None => panic!("Expected a number in line {}", i + 2),
+            };
+            match parts.first() {
+                Some(&"NAME") => module.name = parts[1..].join(" "),
+                Some(&"ORG") => module.origin = Some(number(parts.get(1))),
+                Some(&"CODE") => module.code = assembly::string_to_bytes(&parts[1..].concat()),
+                Some(&"EXPORT") => module.exports.push(Export { name: parts.get(1).unwrap_or(&"").to_string(), value: number(parts.get(2)), relocatable: parts.get(3) == Some(&"LABEL") }),
+                Some(&"IMPORT") => module.imports.extend(parts[1..].iter().map(|x| x.to_string())),
+                Some(&"RELOC") => module.relocations.push(Relocation { offset: number(parts.get(1)) as usize, symbol: parts.get(2).map(|x| x.to_string()) }),
+                Some(part) => panic!("Unknown record '{part}' in line {}", i + 2),
+                None => {}
+            }
+        }
+        module
+    }
+}
+
+/// Where a module was placed in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub name: String,
+    pub start: i32,
+    pub size: i32,
+}
+
+/// # LinkedProgram
+/// The result of linking modules together, a single image that starts at address 0
+#[derive(Debug, Clone)]
+pub struct LinkedProgram {
+    pub bytes: Vec<Byte>,
+    pub placements: Vec<Placement>,
+    /// Every exported symbol with its final value
+    pub symbols: Vec<(String, i32)>,
+}
+impl LinkedProgram {
+    /// returns the linked program as '0' and '1' characters
+    pub fn binary(&self) -> String {
+        self.bytes.iter().map(|x| x.to_string()).collect()
+    }
+    /// # memory_map
+    /// Shows where every module was placed and the final value of the exported symbols
+    pub fn memory_map(&self) -> String {
+        let mut out = format!("{:<5}  {:<5}  {:>4}  {}\n", "START", "END", "SIZE", "MODULE");
+        for placement in &self.placements {
+            let end = if placement.size == 0 { placement.start } else { placement.start + placement.size - 1 };
+            out += format!("{:02x}     {:02x}     {:>4}  {}\n", placement.start, end, placement.size, placement.name).as_str();
+        }
+        out += format!("{:<5}  {:<5}  {:>4}  free\n", "", "", 256 - self.placements.iter().map(|x| x.size).sum::<i32>()).as_str();
+        out += "\nSYMBOL                VALUE  HEX\n";
+        for (name, value) in &self.symbols {
+            out += format!("{name:<20}  {value:>5}  {value:02x}\n").as_str();
+        }
+        out
+    }
+}
+
+/// # link
+/// Links object modules into a single program.
+/// Modules with an `%ORG` are placed at their address first, the other modules are placed in order in the first space they fit.
+/// The addresses inside each module and the `%EXTERN` names are then patched to their final values.
+/// # Arguments
+/// * `modules` - The modules to link
+/// # Returns
+/// * `LinkedProgram` - The linked program
+/// # Examples
+/// ```
+/// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+/// use vc_8bit::linker::{link, ObjectModule};
+/// let options = AssemblyOptions::default();
+/// let main = ObjectModule::from_program("main", &assemble_program("%EXTERN print\nJMP print", &options));
+/// let print = ObjectModule::from_program("print", &assemble_program("%GLOBAL print\nprint: OUT R0\nHALT", &options));
+/// let program = link(&[main, print]);
+/// assert_eq!(program.binary(), "11101000000000101101100011111111");
+/// ```
+/// # Panics
+/// Will panic if modules overlap, a symbol is exported twice, an `%EXTERN` name is not exported by any module or the program does not fit in memory
+/// ```should_panic
+/// use vc_8bit::assembly::{assemble_program, AssemblyOptions};
+/// use vc_8bit::linker::{link, ObjectModule};
+/// let main = ObjectModule::from_program("main", &assemble_program("%EXTERN print\nJMP print", &AssemblyOptions::default()));
+/// link(&[main]);
+/// ```
+pub fn link(modules: &[ObjectModule]) -> LinkedProgram {
+    let mut placements: Vec<Placement> = vec![];
+    let mut starts: Vec<i32> = vec![0; modules.len()];
+    let overlaps = |placements: &[Placement], start: i32, size: i32| placements.iter().find(|x| size > 0 && x.size > 0 && start < x.start + x.size && x.start < start + size).cloned();
+
+    for (i, module) in modules.iter().enumerate() {
+        if let Some(origin) = module.origin {
+            let size = module.code.len() as i32;
+            if let Some(other) = overlaps(&placements, origin, size) {
+                panic!("Module '{}' at {origin} overlaps module '{}' at {}", module.name, other.name, other.start);
+            }
+            if origin + size > 256 {
+                panic!("Module '{}' at {origin} does not fit in memory", module.name);
+            }
+            starts[i] = origin;
+            placements.push(Placement { name: module.name.clone(), start: origin, size });
+        }
+    }
+    for (i, module) in modules.iter().enumerate() {
+        if module.origin.is_none() {
+            let size = module.code.len() as i32;
+            let mut start = 0;
+            while let Some(other) = overlaps(&placements, start, size) {
+                start = other.start + other.size;
+            }
+            if start + size > 256 {
+                panic!("Module '{}' does not fit in memory", module.name);
+            }
+            starts[i] = start;
+            placements.push(Placement { name: module.name.clone(), start, size });
+        }
+    }
+
+    let mut symbols: Vec<(String, i32)> = vec![];
+    for (i, module) in modules.iter().enumerate() {
+        for export in &module.exports {
+            if let Some(other) = modules.iter().take(i).find(|x| x.exports.iter().any(|e| e.name == export.name)) {
+                panic!("'{}' is exported by both '{}' and '{}'", export.name, other.name, module.name);
+            }
+            let value = if export.relocatable { export.value - module.origin.unwrap_or(0) + starts[i] } else { export.value };
+            symbols.push((export.name.clone(), value));
+        }
+    }
+
+    let size = placements.iter().map(|x| x.start + x.size).max().unwrap_or(0);
+    let mut bytes = vec![Byte::zero(); size as usize];
+    for (i, module) in modules.iter().enumerate() {
+        let start = starts[i] as usize;
+        bytes[start..start + module.code.len()].copy_from_slice(&module.code);
+        for relocation in &module.relocations {
+            let value = match &relocation.symbol {
+                Some(name) => match symbols.iter().find(|x| &x.0 == name) {
+                    Some(symbol) => symbol.1,
+                    None => panic!("'{name}' used in '{}' is not exported by any module", module.name),
+                },
+                None => module.code[relocation.offset].to_i32() - module.origin.unwrap_or(0) + starts[i],
+            };
+            bytes[start + relocation.offset] = Byte::try_from(value).unwrap();
+        }
+    }
+    placements.sort_by_key(|x| x.start);
+    LinkedProgram { bytes, placements, symbols }
+}
@@ -0,0 +1,245 @@
+use crate::assembly;
+use crate::vc_8bit::{Byte, ISA_REVISION, RAM};
+use crate::vcimg::{self, Segment, SegmentKind, VcImage};
+
+/// The bytes every header image starts with
+pub const IMAGE_MAGIC: &[u8; 3] = b"VC8";
+
+/// The formats a program can be loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// The bytes of the program as they are
+    Raw,
+    /// '0' and '1' characters, the format the assembler writes. Whitespace and `;` comments are ignored
+    BitText,
+    /// Intel HEX records
+    IntelHex,
+    /// `VC8`, the load address and the entry point, followed by the bytes of the program
+    Header,
+    /// The `.vcimg` container with segments and a CRC, see `vcimg::VcImage`
+    VcImg,
+}
+impl ImageFormat {
+    /// # detect
+    /// Guesses the format of a file from its contents
+    /// # Examples
+    /// ```
+    /// use vc_8bit::loader::ImageFormat;
+    /// assert_eq!(ImageFormat::detect(b"11111111 ; HALT"), ImageFormat::BitText);
+    /// assert_eq!(ImageFormat::detect(b":01000000FF00\n:00000001FF"), ImageFormat::IntelHex);
+    /// assert_eq!(ImageFormat::detect(&[0xff]), ImageFormat::Raw);
+    /// ```
+    pub fn detect(contents: &[u8]) -> ImageFormat {
+        if contents.starts_with(IMAGE_MAGIC) {
+            return ImageFormat::Header;
+        }
+        if contents.starts_with(vcimg::VCIMG_MAGIC) {
+            return ImageFormat::VcImg;
+        }
+        let text = match std::str::from_utf8(contents) {
+            Ok(text) => text,
+            Err(_) => return ImageFormat::Raw,
+        };
+        if text.trim_start().starts_with(':') {
+            ImageFormat::IntelHex
+        }
+        else if !text.trim().is_empty() && text.lines().all(|x| x.split(';').next().unwrap_or("").chars().all(|c| c == '0' || c == '1' || c.is_whitespace())) {
+            ImageFormat::BitText
+        }
+        else {
+            ImageFormat::Raw
+        }
+    }
+}
+
+/// # ProgramImage
+/// A program ready to be put in the RAM of a computer, along with where it goes and where it starts running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramImage {
+    pub bytes: Vec<Byte>,
+    /// The address the first byte is put at
+    pub load_address: i32,
+    /// The address the program starts running at
+    pub entry: i32,
+}
+impl ProgramImage {
+    /// Creates an image that is loaded at address 0 and starts running there
+    pub fn new(bytes: Vec<Byte>) -> ProgramImage {
+        ProgramImage { bytes, load_address: 0, entry: 0 }
+    }
+    /// Sets the address the image is loaded at. The entry point moves with it
+    pub fn at(mut self, load_address: i32) -> ProgramImage {
+        self.entry += load_address - self.load_address;
+        self.load_address = load_address;
+        self
+    }
+    /// Sets the address the program starts running at
+    pub fn with_entry(mut self, entry: i32) -> ProgramImage {
+        self.entry = entry;
+        self
+    }
+    /// # parse
+    /// Reads an image in the given format
+    /// # Arguments
+    /// * `contents` - The contents of the file
+    /// * `format` - The format of the contents
+    /// # Examples
+    /// ```
+    /// use vc_8bit::loader::{ImageFormat, ProgramImage};
+    /// let image = ProgramImage::parse(b"11001000 00000101 ; MOV R0 5\n11111111 ; HALT", ImageFormat::BitText);
+    /// assert_eq!(image.bytes.len(), 3);
+    /// let image = ProgramImage::parse(&[b'V', b'C', b'8', 16, 18, 0xc8, 5, 0xff], ImageFormat::Header);
+    /// assert_eq!((image.load_address, image.entry, image.bytes.len()), (16, 18, 3));
+    /// ```
+    /// # Panics
+    /// Will panic if the contents are not valid for the format or the program does not fit in memory
+    pub fn parse(contents: &[u8], format: ImageFormat) -> ProgramImage {
+        let image = match format {
+            ImageFormat::Raw => ProgramImage::new(contents.iter().map(|x| Byte::from_u8(*x)).collect()),
+            ImageFormat::BitText => ProgramImage::new(assembly::string_to_bytes(&bit_text(contents))),
+            ImageFormat::IntelHex => intel_hex(contents),
+            ImageFormat::Header => {
+                if contents.len() < 5 || !contents.starts_with(IMAGE_MAGIC) {
+                    panic!("Header image has to start with 'VC8', the load address and the entry point");
+                }
+                ProgramImage { bytes: contents[5..].iter().map(|x| Byte::from_u8(*x)).collect(), load_address: contents[3] as i32, entry: contents[4] as i32 }
+            }
+            ImageFormat::VcImg => {
+                let image = VcImage::from_bytes(contents);
+                if image.isa_revision != ISA_REVISION {
+                    panic!("Image was made for ISA revision {} but the VC runs revision {ISA_REVISION}", image.isa_revision);
+                }
+                // the segments are put in one run of bytes with zeros between them
+                let end = image.segments.iter().map(|x| x.address + x.bytes.len() as i32).max().unwrap_or(image.load_address);
+                let mut bytes = vec![Byte::zero(); (end - image.load_address).max(0) as usize];
+                for segment in &image.segments {
+                    let start = (segment.address - image.load_address) as usize;
+                    bytes[start..start + segment.bytes.len()].copy_from_slice(&segment.bytes);
+                }
+                ProgramImage { bytes, load_address: image.load_address, entry: image.entry }
+            }
+        };
+        if image.load_address + image.bytes.len() as i32 > 256 {
+            panic!("Program of {} bytes at {} does not fit in memory", image.bytes.len(), image.load_address);
+        }
+        image
+    }
+    /// # from_file
+    /// Reads an image from a file, guessing the format from its contents
+    /// # Panics
+    /// Will panic if the file can't be read or is not a valid image
+    pub fn from_file(path: &str) -> ProgramImage {
+        let contents = match std::fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => panic!("Could not read file '{path}': {e}"),
+        };
+        ProgramImage::parse(&contents, ImageFormat::detect(&contents))
+    }
+    /// # to_format
+    /// Writes the image in the given format, the load address and entry point are lost for `Raw` and `BitText`
+    /// # Examples
+    /// ```
+    /// use vc_8bit::loader::{ImageFormat, ProgramImage};
+    /// use vc_8bit::assembly::string_to_bytes;
+    /// let image = ProgramImage::new(string_to_bytes("1100100000000101")).at(32);
+    /// let hex = image.to_format(ImageFormat::IntelHex);
+    /// assert_eq!(String::from_utf8(hex.clone()).unwrap(), ":02002000C80511\n:0400000500000020D7\n:00000001FF\n");
+    /// assert_eq!(ProgramImage::parse(&hex, ImageFormat::IntelHex), image);
+    /// ```
+    pub fn to_format(&self, format: ImageFormat) -> Vec<u8> {
+        let bytes = self.bytes.iter().map(|x| x.to_i32() as u8).collect::<Vec<u8>>();
+        match format {
+            ImageFormat::Raw => bytes,
+            ImageFormat::BitText => self.bytes.iter().map(|x| x.to_string()).collect::<String>().into_bytes(),
+            ImageFormat::IntelHex => {
+                let mut out = String::new();
+                for (i, chunk) in bytes.chunks(16).enumerate() {
+                    out += &hex_record(0, (self.load_address as usize + i * 16) as u16, chunk);
+                }
+                out += &hex_record(5, 0, &(self.entry as u32).to_be_bytes());
+                out += &hex_record(1, 0, &[]);
+                out.into_bytes()
+            }
+            ImageFormat::Header => [IMAGE_MAGIC.as_slice(), &[self.load_address as u8, self.entry as u8], &bytes].concat(),
+            ImageFormat::VcImg => {
+                let segments = vec![Segment { kind: SegmentKind::Code, address: self.load_address, bytes: self.bytes.clone() }];
+                VcImage { isa_revision: ISA_REVISION, entry: self.entry, load_address: self.load_address, segments, symbols: None }.to_bytes()
+            }
+        }
+    }
+    /// # load
+    /// Puts the image in the RAM and sets the RAM index to the entry point, so `Computer::run` starts there
+    /// # Examples
+    /// ```
+    /// use vc_8bit::loader::ProgramImage;
+    /// use vc_8bit::vc_8bit::{Byte, RAM};
+    /// use vc_8bit::assembly::string_to_bytes;
+    /// let mut ram = RAM::new();
+    /// ProgramImage::new(string_to_bytes("1100100000000101")).at(16).load(&mut ram);
+    /// assert_eq!(ram.read(Byte::try_from(17).unwrap()).to_i32(), 5);
+    /// assert_eq!(ram.get_index().to_i32(), 16);
+    /// ```
+    pub fn load(&self, ram: &mut RAM) {
+        ram.insert_bytes_at(Byte::try_from(self.load_address).unwrap(), self.bytes.clone());
+        ram.set_index(Byte::try_from(self.entry).unwrap());
+    }
+}
+
+/// Removes the comments and whitespace from bit text
+fn bit_text(contents: &[u8]) -> String {
+    let text = String::from_utf8_lossy(contents);
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let code = line.split(';').next().unwrap_or("");
+        if let Some(c) = code.chars().find(|c| *c != '0' && *c != '1' && !c.is_whitespace()) {
+            panic!("Unexpected '{c}' in bit text in line {}", i + 1);
+        }
+        out += &code.split_whitespace().collect::<String>();
+    }
+    if !out.len().is_multiple_of(8) {
+        panic!("Bit text has {} bits, which is not a whole number of bytes", out.len());
+    }
+    out
+}
+
+/// Reads Intel HEX records. The image starts at the lowest address with data, gaps are filled with zeros
+fn intel_hex(contents: &[u8]) -> ProgramImage {
+    let text = String::from_utf8_lossy(contents);
+    let mut data: Vec<(usize, u8)> = vec![];
+    let mut entry: Option<i32> = None;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record = match line.strip_prefix(':').map(|x| (0..x.len()).step_by(2).map(|j| x.get(j..j + 2).and_then(|h| u8::from_str_radix(h, 16).ok())).collect::<Option<Vec<u8>>>()) {
+            Some(Some(record)) if record.len() >= 5 && record.len() == record[0] as usize + 5 => record,
+            _ => panic!("Invalid Intel HEX record in line {}", i + 1),
+        };
+        if record.iter().fold(0u8, |sum, x| sum.wrapping_add(*x)) != 0 {
+            panic!("Wrong checksum in Intel HEX record in line {}", i + 1);
+        }
+        let address = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let values = &record[4..record.len() - 1];
+        match record[3] {
+            0 => data.extend(values.iter().enumerate().map(|(j, x)| (address + j, *x))),
+            1 => break,
+            3 | 5 => entry = Some(values.iter().fold(0, |value, x| (value << 8) | *x as i32)),
+            kind => panic!("Unsupported Intel HEX record type {kind:02X} in line {}", i + 1),
+        }
+    }
+    let start = data.iter().map(|x| x.0).min().unwrap_or(0);
+    let end = data.iter().map(|x| x.0 + 1).max().unwrap_or(0);
+    let mut bytes = vec![0u8; end - start];
+    for (address, value) in data {
+        bytes[address - start] = value;
+    }
+    ProgramImage { bytes: bytes.iter().map(|x| Byte::from_u8(*x)).collect(), load_address: start as i32, entry: entry.unwrap_or(start as i32) }
+}
+
+/// Writes one Intel HEX record with its checksum
+fn hex_record(kind: u8, address: u16, values: &[u8]) -> String {
+    let record = [&[values.len() as u8], address.to_be_bytes().as_slice(), &[kind], values].concat();
+    let checksum = record.iter().fold(0u8, |sum, x| sum.wrapping_add(*x)).wrapping_neg();
+    format!(":{}{checksum:02X}\n", record.iter().map(|x| format!("{x:02X}")).collect::<String>())
+}
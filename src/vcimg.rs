@@ -0,0 +1,207 @@
+use crate::vc_8bit::Byte;
+
+/// The bytes every `.vcimg` file starts with
+pub const VCIMG_MAGIC: &[u8; 4] = b"VCIM";
+/// The version of the `.vcimg` layout written by `VcImage::to_bytes`
+pub const VCIMG_VERSION: u8 = 1;
+
+/// What a segment of a program holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SegmentKind {
+    /// Instructions
+    #[default]
+    Code,
+    /// Values the program changes while it runs
+    Data,
+    /// Values the program only reads
+    ReadOnly,
+}
+impl SegmentKind {
+    /// # from_name
+    /// Gets the segment kind from the name used with `%SEGMENT`
+    /// # Examples
+    /// ```
+    /// use vc_8bit::vcimg::SegmentKind;
+    /// assert_eq!(SegmentKind::from_name("rodata"), Some(SegmentKind::ReadOnly));
+    /// ```
+    pub fn from_name(name: &str) -> Option<SegmentKind> {
+        match name.to_uppercase().as_str() {
+            "CODE" => Some(SegmentKind::Code),
+            "DATA" => Some(SegmentKind::Data),
+            "RODATA" => Some(SegmentKind::ReadOnly),
+            _ => None,
+        }
+    }
+    fn to_u8(self) -> u8 {
+        match self {
+            SegmentKind::Code => 0,
+            SegmentKind::Data => 1,
+            SegmentKind::ReadOnly => 2,
+        }
+    }
+}
+
+/// A run of bytes that is loaded at an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub address: i32,
+    pub bytes: Vec<Byte>,
+}
+
+/// A name from the program and its value, kept in the image for debuggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageSymbol {
+    pub name: String,
+    pub value: i32,
+    /// `true` for labels, `false` for `%ASSIGN` constants
+    pub is_label: bool,
+}
+
+/// # VcImage
+/// A program packed in the `.vcimg` container. All numbers are little endian.
+/// ```text
+/// magic         4 bytes  "VCIM"
+/// version       1 byte   layout version, 1
+/// isa revision  1 byte   the instruction set the program was assembled for
+/// entry         1 byte   the address the program starts running at
+/// load address  1 byte   the lowest address of any segment
+/// flags         1 byte   bit 0 is set when there is a symbol table
+/// segments      1 byte   number of segments
+/// per segment   kind (0 code, 1 data, 2 read-only), address, length (2 bytes)
+/// segment bytes in the same order as the table
+/// symbols       2 bytes  number of symbols, only when flag bit 0 is set
+/// per symbol    value, kind (0 label, 1 constant), name length, name
+/// crc           4 bytes  CRC-32 of everything before it
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcImage {
+    pub isa_revision: u8,
+    pub entry: i32,
+    pub load_address: i32,
+    pub segments: Vec<Segment>,
+    pub symbols: Option<Vec<ImageSymbol>>,
+}
+impl VcImage {
+    /// # to_bytes
+    /// Writes the image in the `.vcimg` layout
+    /// # Examples
+    /// ```
+    /// use vc_8bit::vcimg::{Segment, SegmentKind, VcImage};
+    /// use vc_8bit::vc_8bit::ISA_REVISION;
+    /// use vc_8bit::assembly::string_to_bytes;
+    /// let image = VcImage { isa_revision: ISA_REVISION, entry: 0, load_address: 0, segments: vec![Segment { kind: SegmentKind::Code, address: 0, bytes: string_to_bytes("11111111") }], symbols: None };
+    /// let bytes = image.to_bytes();
+    /// assert_eq!(&bytes[0..4], b"VCIM");
+    /// assert_eq!(VcImage::from_bytes(&bytes), image);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = VCIMG_MAGIC.to_vec();
+        out.extend([VCIMG_VERSION, self.isa_revision, self.entry as u8, self.load_address as u8, self.symbols.is_some() as u8, self.segments.len() as u8]);
+        for segment in &self.segments {
+            out.extend([segment.kind.to_u8(), segment.address as u8]);
+            out.extend((segment.bytes.len() as u16).to_le_bytes());
+        }
+        for segment in &self.segments {
+            out.extend(segment.bytes.iter().map(|x| x.to_i32() as u8));
+        }
+        if let Some(symbols) = &self.symbols {
+            out.extend((symbols.len() as u16).to_le_bytes());
+            for symbol in symbols {
+                out.extend([symbol.value as u8, !symbol.is_label as u8, symbol.name.len() as u8]);
+                out.extend(symbol.name.as_bytes());
+            }
+        }
+        out.extend(crc32(&out).to_le_bytes());
+        out
+    }
+    /// # from_bytes
+    /// Reads an image in the `.vcimg` layout
+    /// # Panics
+    /// Will panic if the magic number, version or CRC are wrong, or the image is cut short
+    /// ```should_panic
+    /// use vc_8bit::vcimg::VcImage;
+    /// VcImage::from_bytes(b"VCIM\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00");
+    /// ```
+    pub fn from_bytes(contents: &[u8]) -> VcImage {
+        if !contents.starts_with(VCIMG_MAGIC) {
+            panic!("Not a .vcimg file, it has to start with 'VCIM'");
+        }
+        if contents.len() < 14 {
+            panic!(".vcimg file is cut short");
+        }
+        let (body, crc) = contents.split_at(contents.len() - 4);
+        if crc32(body).to_le_bytes() != crc {
+            panic!(".vcimg file is corrupt, the CRC does not match");
+        }
+        if body[4] != VCIMG_VERSION {
+            panic!(".vcimg version {} is not supported, expected version {VCIMG_VERSION}", body[4]);
+        }
+
+        let mut reader = Reader { bytes: body, index: 10 };
+        let table = (0..body[9]).map(|_| (reader.u8(), reader.u8(), reader.u16())).collect::<Vec<(u8, u8, u16)>>();
+        let mut segments: Vec<Segment> = vec![];
+        for (kind, address, length) in table {
+            let kind = match kind {
+                0 => SegmentKind::Code,
+                1 => SegmentKind::Data,
+                2 => SegmentKind::ReadOnly,
+                _ => panic!("Unknown segment kind {kind} in .vcimg file"),
+            };
+            let bytes = (0..length).map(|_| Byte::from_u8(reader.u8())).collect();
+            segments.push(Segment { kind, address: address as i32, bytes });
+        }
+        let symbols = if body[8] & 1 == 1 {
+            let count = reader.u16();
+            Some((0..count).map(|_| {
+                let value = reader.u8() as i32;
+                let is_label = reader.u8() == 0;
+                let length = reader.u8();
+                let name = (0..length).map(|_| reader.u8() as char).collect();
+                ImageSymbol { name, value, is_label }
+            }).collect())
+        }
+        else {
+            None
+        };
+        VcImage { isa_revision: body[5], entry: body[6] as i32, load_address: body[7] as i32, segments, symbols }
+    }
+}
+
+/// Reads the numbers of an image one after another
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+impl Reader<'_> {
+    fn u8(&mut self) -> u8 {
+        match self.bytes.get(self.index) {
+            Some(value) => {
+                self.index += 1;
+                *value
+            }
+            None => panic!(".vcimg file is cut short"),
+        }
+    }
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+}
+
+/// # crc32
+/// The CRC-32 used by zip and PNG, used to find corrupt images
+/// # Examples
+/// ```
+/// use vc_8bit::vcimg::crc32;
+/// assert_eq!(crc32(b"123456789"), 0xcbf43926);
+/// ```
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}